@@ -1,178 +1,732 @@
-use color_eyre::Result;
-use rand::Rng;
+use color_eyre::{eyre::bail, Result};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use std::{collections::HashMap, sync::Arc, time::Instant};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use common::{IdGenerator, Message, MsgId, MsgIdAble, Node, NodeIdable};
-use crossbeam::channel::{Receiver, Sender, TryRecvError};
+use common::{
+    jitter, setting, Body, IdGenerator, Message, MsgId, MsgIdAble, Node, NodeIdable, Outbox,
+    TimerId, Timers,
+};
 
-use crate::{Broadcast, RequestBody, RequestHandler};
+use crate::{Broadcast, BroadcastRequest, GossipValue, TopologyStrategy};
 
+/// A value we owe a peer
 #[derive(Debug, Clone)]
 pub struct Job {
-    broadcast: Broadcast,
-    dest: String,
+    value: GossipValue,
+    /// The batch this was last sent in, if it has been sent
+    batch: Option<MsgId>,
+    /// When to send it, or resend it if the batch goes unacked
     run_at: Instant,
     attempts: u64,
 }
 
-impl Job {
-    fn send(&mut self, stdout_sender: &Sender<String>, node_id: String) {
-        let m = Message {
-            body: RequestBody::Broadcast(self.broadcast.clone()),
-            dest: self.dest.clone(),
-            src: node_id,
-        };
-        let output = serde_json::to_string(&m).unwrap();
+/// What we know about one of our neighbours
+#[derive(Debug, Default)]
+struct Peer {
+    /// Values the peer is known to have, which we never need to send it again
+    known: HashSet<Value>,
+    /// Values we still owe the peer, sent or not
+    jobs: Vec<Job>,
+    /// The timer that sends the peer its jobs, and when it's set for
+    timer: Option<(TimerId, Instant)>,
+}
+
+impl Peer {
+    fn learn(&mut self, value: Value) {
+        self.jobs.retain(|job| job.value.message != value);
+        self.known.insert(value);
+    }
+
+    fn acked(&mut self, batch: MsgId, now: Instant) {
+        let acked: Vec<Value> = self
+            .jobs
+            .iter()
+            .filter(|job| job.batch == Some(batch))
+            .map(|job| job.value.message.clone())
+            .collect();
+        if acked.is_empty() {
+            return;
+        }
+        for value in acked {
+            self.learn(value);
+        }
+
+        // Batch ids only go up, and the peer is reachable, so any batch sent before this one
+        // that is still unacked was probably lost. Resend it now rather than waiting out its
+        // backoff
+        for job in &mut self.jobs {
+            if job.batch.is_some_and(|b| b < batch) {
+                job.run_at = job.run_at.min(now);
+            }
+        }
+    }
+}
+
+/// How long a new value waits before its first send, so it can be batched up with others
+const FIRST_SEND_DELAY: Duration = Duration::from_millis(700);
+/// Up to this much is added to the first send, so nodes that hear of a value together don't
+/// all pass it on at once
+const FIRST_SEND_JITTER: Duration = Duration::from_millis(1000);
+
+/// How long to wait for a peer to ack a batch before resending it, and when to give up
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub initial_delay: Duration,
+    pub multiplier: f64,
+    pub max_delay: Duration,
+    /// Up to this much is added to every delay, so retries to a slow peer don't line up
+    pub jitter: Duration,
+    /// Give up on a value after this many sends, leaving it to anti-entropy. `None` retries
+    /// forever
+    pub max_attempts: Option<u64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_secs(1),
+            multiplier: 2.0,
+            max_delay: Duration::from_secs(5),
+            jitter: Duration::from_millis(200),
+            max_attempts: None,
+        }
+    }
+}
 
-        stdout_sender.send(output).unwrap();
+impl RetryPolicy {
+    /// How long to wait after a value's `attempts`th send
+    pub fn delay(&self, attempts: u64) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(i32::MAX as u64) as i32;
+        let backoff = (self.initial_delay.as_secs_f64() * self.multiplier.powi(exponent))
+            .min(self.max_delay.as_secs_f64())
+            .max(0.0);
 
-        self.attempts += 1;
+        Duration::from_secs_f64(backoff) + jitter(self.jitter)
+    }
+
+    /// The default policy, with each setting overridden by its flag, or failing that its
+    /// environment variable
+    ///
+    /// | Flag                   | Variable                       |
+    /// |------------------------|--------------------------------|
+    /// | `--retry-initial-ms`   | `BROADCAST_RETRY_INITIAL_MS`   |
+    /// | `--retry-multiplier`   | `BROADCAST_RETRY_MULTIPLIER`   |
+    /// | `--retry-max-ms`       | `BROADCAST_RETRY_MAX_MS`       |
+    /// | `--retry-jitter-ms`    | `BROADCAST_RETRY_JITTER_MS`    |
+    /// | `--retry-max-attempts` | `BROADCAST_RETRY_MAX_ATTEMPTS` |
+    pub fn configured() -> Result<Self> {
+        let mut policy = Self::default();
+
+        if let Some(ms) = setting("--retry-initial-ms", "BROADCAST_RETRY_INITIAL_MS") {
+            policy.initial_delay = Duration::from_millis(ms.parse()?);
+        }
+        if let Some(multiplier) = setting("--retry-multiplier", "BROADCAST_RETRY_MULTIPLIER") {
+            policy.multiplier = multiplier.parse()?;
+        }
+        if let Some(ms) = setting("--retry-max-ms", "BROADCAST_RETRY_MAX_MS") {
+            policy.max_delay = Duration::from_millis(ms.parse()?);
+        }
+        if let Some(ms) = setting("--retry-jitter-ms", "BROADCAST_RETRY_JITTER_MS") {
+            policy.jitter = Duration::from_millis(ms.parse()?);
+        }
+        if let Some(max) = setting("--retry-max-attempts", "BROADCAST_RETRY_MAX_ATTEMPTS") {
+            policy.max_attempts = Some(max.parse()?);
+        }
+
+        policy.validated()
+    }
 
-        let delay = std::time::Duration::from_millis(self.attempts * 1000);
-        self.run_at = std::time::Instant::now() + delay;
+    /// Rejects a policy whose delays would shrink, or that gives up before the first send
+    fn validated(self) -> Result<Self> {
+        if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            bail!(
+                "The retry multiplier must be at least 1, not {}",
+                self.multiplier
+            );
+        }
+        if self.max_attempts == Some(0) {
+            bail!("The retry max attempts must be at least 1");
+        }
+
+        Ok(self)
+    }
+
+    fn exhausted(&self, attempts: u64) -> bool {
+        self.max_attempts.is_some_and(|max| attempts >= max)
     }
 }
 
 pub struct GossipManager {
-    reciever: Receiver<GossipMsg>,
-    // gossip_queue: Vec<Job>,
-    stdout_sender: Sender<String>,
+    outbox: Outbox,
     node_id: String,
 
+    strategy: Box<dyn TopologyStrategy>,
+
     /// Nearest Peers
     topology: Vec<String>,
 
     ids: Arc<IdGenerator>,
 
-    to_gossip: HashMap<String, Vec<Job>>,
+    peers: HashMap<String, Peer>,
+
+    retry_policy: RetryPolicy,
 }
 
 impl GossipManager {
-    pub fn new(reciever: Receiver<GossipMsg>, stdout_sender: Sender<String>, node: &Node) -> Self {
+    pub fn new(outbox: Outbox, node: &Node, strategy: Box<dyn TopologyStrategy>) -> Self {
+        let topology = strategy.neighbours(node.node_id(), &node.peers);
+
         Self {
-            reciever,
-            stdout_sender,
+            outbox,
             node_id: node.node_id().to_owned(),
-            topology: node.peers.clone(),
+            strategy,
+            topology,
             ids: Arc::clone(&node.ids),
-            to_gossip: HashMap::new(),
+            peers: HashMap::new(),
+            retry_policy: RetryPolicy::default(),
         }
     }
 
-    pub fn handle_gossip(mut self) -> Result<()> {
-        loop {
-            let msg = self.reciever.try_recv();
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
 
-            match msg {
-                Ok(GossipMsg::Topology(topology)) => {
-                    // self.topology = topology;
+    pub fn handle_msg(&mut self, msg: GossipMsg) {
+        match msg {
+            GossipMsg::Topology(topology) => match self.strategy.on_topology(&topology) {
+                Some(topology) => self.topology = topology,
+                None => eprintln!(
+                    "Ignoring Maelstrom's topology in favour of {:?}",
+                    self.strategy
+                ),
+            },
+            GossipMsg::Gossip {
+                value:
+                    GossipValue {
+                        message,
+                        origin,
+                        hops,
+                        already_sent_to,
+                    },
+                from,
+            } => {
+                if let Some(from) = from {
+                    self.learn(&from, message.clone());
                 }
-                Ok(GossipMsg::Gossip {
-                    msg,
-                    already_sent_to,
-                }) => {
-                    // Lets see if our gossip queue can be trimmed based on this info
-                    // self.gossip_queue.retain(|in_queue| {
-                    //     in_queue.broadcast.message != msg
-                    //         && !already_sent_to.contains(&in_queue.dest)
-                    // });
-
-                    let now = std::time::Instant::now();
-                    let jitter = rand::thread_rng().gen_range(0..1000);
-
-                    let run_at = now + std::time::Duration::from_millis(700 + jitter);
-
-                    let to_send_to = self
-                        .topology
-                        .iter()
-                        .filter(|d| !already_sent_to.contains(d));
-
-                    let mut already_sent_to = already_sent_to.clone();
-                    already_sent_to.append(&mut to_send_to.clone().cloned().collect());
-
-                    for dest in to_send_to {
-                        let broadcast = Broadcast {
-                            msg_id: self.ids.generate_msg_id(),
-                            message: msg,
-                            already_sent_to: already_sent_to.clone(),
-                        };
-                        let job = Job {
-                            broadcast: broadcast.clone(),
-                            dest: dest.clone(),
-                            run_at,
-                            attempts: 0,
-                        };
-
-                        self.to_gossip
-                            .entry(dest.clone())
-                            .or_insert_with(Vec::new)
-                            .push(job);
-                    }
+
+                let run_at = Instant::now() + FIRST_SEND_DELAY + jitter(FIRST_SEND_JITTER);
+
+                // Whoever's been sent it already will pass it on to their own neighbours, but
+                // they haven't acked it yet, so they only get skipped the first time round
+                let to_send_to: Vec<String> = self
+                    .topology
+                    .iter()
+                    .filter(|d| !already_sent_to.contains(d))
+                    .filter(|d| {
+                        self.peers
+                            .get(*d)
+                            .is_none_or(|peer| !peer.known.contains(&message))
+                    })
+                    .cloned()
+                    .collect();
+
+                let mut already_sent_to = already_sent_to.clone();
+                already_sent_to.extend(to_send_to.iter().cloned());
+                // With a partial overlay our neighbours relay onwards, but never back to us
+                if !already_sent_to.contains(&self.node_id) {
+                    already_sent_to.push(self.node_id.clone());
                 }
-                Ok(GossipMsg::GotResponse(in_response_to)) => {
-                    let keys: Vec<_> = self.to_gossip.keys().cloned().collect();
 
-                    for k in keys {
-                        let jobs = self.to_gossip.get_mut(&k).unwrap();
-                        jobs.retain(|job| job.broadcast.msg_id != in_response_to);
-                    }
+                for dest in to_send_to {
+                    let value = GossipValue {
+                        message: message.clone(),
+                        origin: origin.clone(),
+                        hops: hops + 1,
+                        already_sent_to: already_sent_to.clone(),
+                    };
+                    let job = Job {
+                        value,
+                        batch: None,
+                        run_at,
+                        attempts: 0,
+                    };
+
+                    self.peers.entry(dest).or_default().jobs.push(job);
                 }
-                Err(TryRecvError::Disconnected) => break,
-                Err(TryRecvError::Empty) => {
-                    let now = std::time::Instant::now();
-                    let stdout_sender = &self.stdout_sender;
-
-                    // for jobs in self.to_gossip.values() {
-                    //     for job in iobs {
-                    //         if job.run_at <= now {
-                    //             job.send(stdout_sender, self.node_id.clone());
-                    //         }
-                    //     }
-                    // }
-                    let keys: Vec<_> = self.to_gossip.keys().cloned().collect();
-                    for k in keys {
-                        let jobs = self.to_gossip.get_mut(&k).unwrap();
-                        if jobs.is_empty() {
-                            continue;
-                        }
-
-                        let min_run_at = jobs.iter().min_by_key(|job| job.run_at).unwrap().run_at;
-                        if min_run_at > now {
-                            continue;
-                        }
-
-                        let m = Message {
-                            body: RequestBody::BulkBroadcast {
-                                broadcasts: jobs.iter().map(|job| job.broadcast.clone()).collect(),
-                            },
-                            dest: k.clone(),
-                            src: self.node_id.clone(),
-                        };
-                        let output = serde_json::to_string(&m).unwrap();
-
-                        stdout_sender.send(output).unwrap();
-
-                        for j in jobs {
-                            j.attempts += 1;
-
-                            let delay = std::time::Duration::from_millis(j.attempts * 1000);
-                            j.run_at = std::time::Instant::now() + delay;
-                        }
-                    }
+            }
+            GossipMsg::Seen { msg, from } => {
+                if let Some(from) = from {
+                    self.learn(&from, msg);
                 }
+            }
+            GossipMsg::GotResponse(in_response_to) => {
+                let now = Instant::now();
+
+                for peer in self.peers.values_mut() {
+                    peer.acked(in_response_to, now);
+                }
+            }
+        }
+    }
+
+    /// `from` sent us `msg`, so it has it and we needn't send it back
+    ///
+    /// Only what a peer sent us, or acked, tells us what it has. Being in some other node's
+    /// `already_sent_to` only means it was sent the value, which may yet be lost.
+    fn learn(&mut self, from: &str, msg: Value) {
+        if from != self.node_id {
+            self.peers.entry(from.to_owned()).or_default().learn(msg);
+        }
+    }
+
+    /// Send a random neighbour a digest of every value we have, so it can reply with the ones
+    /// we're missing
+    ///
+    /// Gossip gives up on nothing, but a value can still slip through if we were partitioned
+    /// from everyone who had it, so this runs periodically to reconcile whatever it missed.
+    pub fn send_digest(&self, values: &[Value]) -> Result<()> {
+        let Some(dest) = self.topology.choose(&mut rand::thread_rng()) else {
+            return Ok(());
+        };
+
+        let body = Body {
+            msg_id: Some(self.ids.generate_msg_id()),
+            ..Body::new(BroadcastRequest::Sync {
+                digest: Digest::of(values),
+            })
+        };
+        let m = Message::new(&self.node_id, dest, body);
+
+        self.outbox.send(&m)
+    }
+
+    /// Keep a timer set for each peer we owe values, for when its earliest job comes due
+    ///
+    /// Run after anything that adds, acks or resends jobs. A peer's timer is only replaced
+    /// when that time moves, so a steady stream of new values doesn't keep resetting it.
+    pub(crate) fn schedule(&mut self, timers: &mut Timers<Broadcast>) {
+        let now = Instant::now();
+
+        for (dest, peer) in &mut self.peers {
+            let due = peer.jobs.iter().map(|job| job.run_at).min();
+            if peer.timer.map(|(_, at)| at) == due {
+                continue;
+            }
+
+            if let Some((id, _)) = peer.timer.take() {
+                timers.cancel(id);
+            }
+            let Some(due) = due else {
+                continue;
             };
+
+            let dest = dest.clone();
+            let id = timers.after(due.saturating_duration_since(now), move |broadcast| {
+                broadcast.send_gossip(&dest)
+            });
+            peer.timer = Some((id, due));
+        }
+    }
+
+    /// Send `dest` the values it doesn't have yet, once its timer fires
+    ///
+    /// Jobs still waiting on an ack from an earlier batch stay out until their retry is due,
+    /// so each batch only carries what's new since then plus what timed out. Every batch gets
+    /// a fresh msg_id, and its ack clears every job in it.
+    pub fn send_due(&mut self, dest: &str, now: Instant) -> Result<()> {
+        let Some(peer) = self.peers.get_mut(dest) else {
+            return Ok(());
+        };
+        // It has fired, so the next `schedule` needs to set another
+        peer.timer = None;
+
+        peer.jobs.retain(|job| {
+            let dead = job.run_at <= now && self.retry_policy.exhausted(job.attempts);
+            if dead {
+                eprintln!(
+                    "Dead letter: giving up on sending {} to {dest} after {} attempts",
+                    job.value.message, job.attempts
+                );
+            }
+
+            !dead
+        });
+
+        if !peer.jobs.iter().any(|job| job.run_at <= now) {
+            return Ok(());
+        }
+
+        let mut due: Vec<&mut Job> = peer
+            .jobs
+            .iter_mut()
+            .filter(|job| job.attempts == 0 || job.run_at <= now)
+            .collect();
+
+        let batch = self.ids.generate_msg_id();
+        let body = Body {
+            msg_id: Some(batch),
+            ..Body::new(BroadcastRequest::Gossip {
+                values: due.iter().map(|job| job.value.clone()).collect(),
+            })
+        };
+        let m = Message::new(&self.node_id, dest, body);
+        self.outbox.send(&m)?;
+
+        for j in &mut due {
+            j.batch = Some(batch);
+            j.attempts += 1;
+            j.run_at = now + self.retry_policy.delay(j.attempts);
         }
 
         Ok(())
     }
+
+    /// Send every value we haven't sent yet, without waiting to batch it up
+    ///
+    /// For when we're shutting down, so nothing only we have is lost with us. Values already
+    /// sent and still unacked are left alone, as we won't be around for the acks anyway.
+    pub fn flush(&mut self) -> Result<()> {
+        let now = Instant::now();
+
+        let mut unacked = 0;
+        for peer in self.peers.values_mut() {
+            for job in &mut peer.jobs {
+                if job.attempts == 0 {
+                    job.run_at = now;
+                } else {
+                    unacked += 1;
+                }
+            }
+        }
+        if unacked > 0 {
+            eprintln!("Shutting down with {unacked} values unacked by our peers");
+        }
+
+        let dests: Vec<String> = self.peers.keys().cloned().collect();
+        for dest in dests {
+            self.send_due(&dest, now)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A set of values, summarised as the inclusive ranges of integers they cover, plus the hash
+/// of anything that isn't an integer
+///
+/// Broadcast values are mostly consecutive integers, so this stays small however many values
+/// there are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Digest {
+    ranges: Vec<(u64, u64)>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default = "Default::default")]
+    hashes: Vec<u64>,
+}
+
+/// Every node hashes the same value the same way, as `DefaultHasher::new` isn't randomly seeded
+fn hash(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+impl Digest {
+    pub fn of(values: &[Value]) -> Self {
+        let mut integers: Vec<u64> = values.iter().filter_map(Value::as_u64).collect();
+        integers.sort_unstable();
+        integers.dedup();
+
+        let mut ranges: Vec<(u64, u64)> = vec![];
+        for v in integers {
+            match ranges.last_mut() {
+                Some((_, end)) if end.checked_add(1) == Some(v) => *end = v,
+                _ => ranges.push((v, v)),
+            }
+        }
+
+        let mut hashes: Vec<u64> = values
+            .iter()
+            .filter(|v| v.as_u64().is_none())
+            .map(hash)
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+
+        Self { ranges, hashes }
+    }
+
+    pub fn contains(&self, value: &Value) -> bool {
+        let Some(value) = value.as_u64() else {
+            return self.hashes.binary_search(&hash(value)).is_ok();
+        };
+
+        let i = self.ranges.partition_point(|(_, end)| *end < value);
+
+        self.ranges.get(i).is_some_and(|(start, _)| *start <= value)
+    }
 }
 
 pub enum GossipMsg {
+    /// A value that's new to us, to pass on, and the peer that sent it if it came from one
     Gossip {
-        msg: u64,
-        already_sent_to: Vec<String>,
+        value: GossipValue,
+        from: Option<String>,
+    },
+    /// A value we already had turned up again
+    Seen {
+        msg: Value,
+        from: Option<String>,
     },
     Topology(Vec<String>),
     GotResponse(MsgId),
 }
+
+#[cfg(test)]
+mod tests {
+    use common::MakeNewNode;
+    use crossbeam::channel::Receiver;
+    use serde_json::json;
+
+    use super::*;
+    use crate::FullMesh;
+
+    /// n1 of four nodes, gossiping to everyone
+    fn manager() -> (GossipManager, Receiver<String>) {
+        let init = json!({
+            "src": "c0",
+            "dest": "n1",
+            "body": {"type": "init", "msg_id": 1, "node_id": "n1", "node_ids": ["n0", "n1", "n2", "n3"]},
+        });
+        let node = Node::init(init.to_string()).unwrap();
+        let (outbox, sent) = crossbeam::channel::unbounded();
+        let manager = GossipManager::new(Outbox::Channel(outbox), &node, Box::new(FullMesh));
+
+        (manager, sent)
+    }
+
+    fn value(message: Value, already_sent_to: &[&str]) -> GossipValue {
+        GossipValue {
+            message,
+            origin: "n0".to_owned(),
+            hops: 0,
+            already_sent_to: already_sent_to.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn owed(manager: &GossipManager, dest: &str) -> Vec<Value> {
+        manager.peers.get(dest).map_or(vec![], |peer| {
+            peer.jobs
+                .iter()
+                .map(|job| job.value.message.clone())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn gossip_skips_the_sender_and_whoever_was_already_sent_it() {
+        let (mut manager, _sent) = manager();
+
+        manager.handle_msg(GossipMsg::Gossip {
+            value: value(json!(1), &["n0", "n2"]),
+            from: Some("n0".to_owned()),
+        });
+
+        assert_eq!(owed(&manager, "n0"), Vec::<Value>::new());
+        assert_eq!(owed(&manager, "n2"), Vec::<Value>::new());
+        assert_eq!(owed(&manager, "n3"), [json!(1)]);
+        assert!(manager.peers["n0"].known.contains(&json!(1)));
+        // n2 was only sent it, so we can't count on it having it
+        assert!(manager
+            .peers
+            .get("n2")
+            .is_none_or(|peer| peer.known.is_empty()));
+    }
+
+    #[test]
+    fn others_sending_a_value_leaves_our_jobs_alone() {
+        let (mut manager, _sent) = manager();
+        manager.handle_msg(GossipMsg::Gossip {
+            value: value(json!(1), &[]),
+            from: None,
+        });
+        assert_eq!(owed(&manager, "n2"), [json!(1)]);
+
+        // n3 says it sent n2 the value too, which may yet be lost
+        manager.handle_msg(GossipMsg::Seen {
+            msg: json!(1),
+            from: Some("n3".to_owned()),
+        });
+        assert_eq!(owed(&manager, "n2"), [json!(1)]);
+        assert_eq!(owed(&manager, "n3"), Vec::<Value>::new());
+
+        // Only n2 itself can tell us it has it
+        manager.handle_msg(GossipMsg::Seen {
+            msg: json!(1),
+            from: Some("n2".to_owned()),
+        });
+        assert_eq!(owed(&manager, "n2"), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn acks_clear_what_they_ack() {
+        let (mut manager, sent) = manager();
+        manager.handle_msg(GossipMsg::Gossip {
+            value: value(json!(1), &[]),
+            from: None,
+        });
+
+        let later = Instant::now() + Duration::from_secs(5);
+        manager.send_due("n2", later).unwrap();
+        let batch = manager.peers["n2"].jobs[0].batch.unwrap();
+        let gossip: Value = serde_json::from_str(&sent.try_recv().unwrap()).unwrap();
+        assert_eq!(gossip["dest"], "n2");
+        assert_eq!(gossip["body"]["msg_id"], batch);
+        manager.handle_msg(GossipMsg::GotResponse(batch));
+
+        assert_eq!(owed(&manager, "n2"), Vec::<Value>::new());
+        assert!(manager.peers["n2"].known.contains(&json!(1)));
+        assert_eq!(owed(&manager, "n3"), [json!(1)]);
+    }
+
+    #[test]
+    fn retries_back_off_up_to_the_max_delay() {
+        let policy = RetryPolicy {
+            jitter: Duration::ZERO,
+            ..RetryPolicy::default()
+        };
+
+        let delays: Vec<u64> = (1..=5).map(|n| policy.delay(n).as_secs()).collect();
+        assert_eq!(delays, [1, 2, 4, 5, 5]);
+
+        let jittered = RetryPolicy::default().delay(1);
+        assert!(jittered >= Duration::from_secs(1));
+        assert!(jittered < Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn retry_policies_that_never_retry_sensibly_are_rejected() {
+        for policy in [
+            RetryPolicy {
+                multiplier: -2.0,
+                ..RetryPolicy::default()
+            },
+            RetryPolicy {
+                multiplier: 0.5,
+                ..RetryPolicy::default()
+            },
+            RetryPolicy {
+                multiplier: f64::NAN,
+                ..RetryPolicy::default()
+            },
+            RetryPolicy {
+                max_attempts: Some(0),
+                ..RetryPolicy::default()
+            },
+        ] {
+            assert!(policy.clone().validated().is_err(), "{policy:?}");
+        }
+
+        assert!(RetryPolicy::default().validated().is_ok());
+    }
+
+    #[test]
+    fn negative_backoff_is_clamped_to_no_delay() {
+        let policy = RetryPolicy {
+            multiplier: -2.0,
+            jitter: Duration::ZERO,
+            ..RetryPolicy::default()
+        };
+
+        assert_eq!(policy.delay(2), Duration::ZERO);
+    }
+
+    #[test]
+    fn values_are_dead_letters_after_max_attempts() {
+        let (manager, _sent) = manager();
+        let mut manager = manager.with_retry_policy(RetryPolicy {
+            max_attempts: Some(2),
+            ..RetryPolicy::default()
+        });
+        manager.handle_msg(GossipMsg::Gossip {
+            value: value(json!(1), &[]),
+            from: None,
+        });
+
+        let mut now = Instant::now();
+        for _ in 0..2 {
+            now += Duration::from_secs(10);
+            manager.send_due("n2", now).unwrap();
+            assert_eq!(owed(&manager, "n2"), [json!(1)]);
+        }
+
+        now += Duration::from_secs(10);
+        manager.send_due("n2", now).unwrap();
+        assert_eq!(owed(&manager, "n2"), Vec::<Value>::new());
+    }
+
+    #[test]
+    fn digest_of_integers_is_ranges() {
+        let digest = Digest::of(&[json!(3), json!(1), json!(2), json!(7), json!(2), json!(9)]);
+
+        assert_eq!(digest.ranges, [(1, 3), (7, 7), (9, 9)]);
+        assert!(digest.hashes.is_empty());
+    }
+
+    #[test]
+    fn digest_contains() {
+        let values = [
+            json!(0),
+            json!(1),
+            json!(5),
+            json!("a"),
+            json!([1, 2]),
+            json!(-1),
+        ];
+        let digest = Digest::of(&values);
+
+        for value in &values {
+            assert!(digest.contains(value), "{value}");
+        }
+        for value in [
+            json!(2),
+            json!(4),
+            json!(6),
+            json!("b"),
+            json!([2, 1]),
+            json!(-2),
+        ] {
+            assert!(!digest.contains(&value), "{value}");
+        }
+    }
+
+    #[test]
+    fn digest_of_nothing() {
+        let digest = Digest::of(&[]);
+
+        assert!(!digest.contains(&json!(0)));
+        assert_eq!(
+            serde_json::to_value(&digest).unwrap(),
+            json!({"ranges": []})
+        );
+    }
+
+    #[test]
+    fn digest_handles_the_largest_integer() {
+        let digest = Digest::of(&[json!(u64::MAX - 1), json!(u64::MAX)]);
+
+        assert_eq!(digest.ranges, [(u64::MAX - 1, u64::MAX)]);
+        assert!(digest.contains(&json!(u64::MAX)));
+    }
+}
@@ -0,0 +1,82 @@
+//! The `broadcast` workload: values broadcast to any node are gossiped to every other, and
+//! survive partitions and lost messages
+//!
+//! [`Broadcast`] is a [`common::Service`], so the `broadcast` binary and any node that runs
+//! several workloads share it.
+
+use std::collections::HashMap;
+
+use common::*;
+
+mod gossip;
+pub use gossip::*;
+
+mod topology;
+pub use topology::*;
+
+mod values;
+pub use values::*;
+
+mod service;
+pub use service::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[request(reply = BroadcastReply)]
+pub enum BroadcastRequest {
+    Broadcast {
+        message: Value,
+    },
+    /// Reads everything by default. `since` a `version` from an earlier read skips the values
+    /// that read returned, and `limit` caps how many come back at once
+    Read {
+        #[serde(default)]
+        since: Option<u64>,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
+    Topology {
+        topology: HashMap<String, Vec<String>>,
+    },
+    /// A batch of values from a peer. Only nodes send these, never clients
+    Gossip {
+        values: Vec<GossipValue>,
+    },
+    /// Peers' replies come to us like any other message, so they're routed here too
+    GossipOk,
+    Sync {
+        digest: Digest,
+    },
+    SyncOk {
+        messages: Vec<Value>,
+    },
+}
+
+#[reply]
+pub enum BroadcastReply {
+    Broadcast,
+    Read {
+        messages: Vec<Value>,
+        /// Pass as `since` to read on from here
+        version: u64,
+    },
+    Topology,
+    Gossip,
+    /// The values the syncing node's digest is missing
+    Sync {
+        messages: Vec<Value>,
+    },
+}
+
+/// A value as it's gossiped between nodes, along with how it got here
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct GossipValue {
+    message: Value,
+    /// The node a client broadcast it to
+    origin: String,
+    /// How many times it has been gossiped on its way to us
+    hops: u32,
+    /// Nodes that have already been sent it, so we needn't send it to them
+    already_sent_to: Vec<String>,
+}
@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use common::{Body, Message, Node, NodeIdable, Outbox, Service, TimerId, Timers};
+use serde_json::Value;
+
+use crate::{
+    canonical, BroadcastReply, BroadcastRequest, GossipManager, GossipMsg, GossipValue,
+    RetryPolicy, TopologyStrategy, Values,
+};
+
+/// Stores every value broadcast to the cluster, gossiping new ones on to our neighbours until
+/// they ack them, and syncing with a neighbour now and then to pick up anything gossip missed
+pub struct Broadcast {
+    node_id: String,
+    recieved_values: Values,
+    gossip_manager: GossipManager,
+    timers: Timers<Broadcast>,
+    anti_entropy: Option<TimerId>,
+}
+
+const ANTI_ENTROPY_INTERVAL: Duration = Duration::from_secs(1);
+const ANTI_ENTROPY_JITTER: Duration = Duration::from_millis(500);
+
+impl Broadcast {
+    pub fn new(
+        node: &Node,
+        strategy: Box<dyn TopologyStrategy>,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            node_id: node.node_id().to_owned(),
+            recieved_values: Values::default(),
+            gossip_manager: GossipManager::new(Outbox::Stdout, node, strategy)
+                .with_retry_policy(retry_policy),
+            timers: Timers::new(),
+            anti_entropy: None,
+        }
+    }
+
+    /// Store `value`, and pass it on if it's new to us
+    ///
+    /// `from` is the peer that sent it, if it came from one, which we then know has it.
+    fn receive(&mut self, value: GossipValue, from: Option<&str>) {
+        let message = canonical(&value.message);
+        let from = from.map(str::to_owned);
+
+        if !self.recieved_values.insert(message.clone()) {
+            self.gossip(GossipMsg::Seen { msg: message, from });
+
+            return;
+        }
+
+        self.gossip(GossipMsg::Gossip {
+            value: GossipValue { message, ..value },
+            from,
+        });
+    }
+
+    fn gossip(&mut self, msg: GossipMsg) {
+        self.gossip_manager.handle_msg(msg);
+        self.gossip_manager.schedule(&mut self.timers);
+    }
+
+    /// Run by `dest`'s gossip timer
+    pub(crate) fn send_gossip(&mut self, dest: &str) -> Result<()> {
+        self.gossip_manager.send_due(dest, Instant::now())?;
+        self.gossip_manager.schedule(&mut self.timers);
+
+        Ok(())
+    }
+
+    /// A value that starts its gossip here
+    fn originate(&self, message: Value) -> GossipValue {
+        GossipValue {
+            message,
+            origin: self.node_id.clone(),
+            hops: 0,
+            already_sent_to: vec![],
+        }
+    }
+
+    /// Start syncing with our neighbours, unless we already are
+    ///
+    /// This waits for the `topology` message, since Maelstrom only sends it once every node is
+    /// initialised, and a node that hears from us before its `init` can't cope.
+    fn start_anti_entropy(&mut self) {
+        if self.anti_entropy.is_some() {
+            return;
+        }
+
+        let id = self.timers.every(
+            ANTI_ENTROPY_INTERVAL,
+            ANTI_ENTROPY_JITTER,
+            |broadcast: &mut Broadcast| {
+                broadcast
+                    .gossip_manager
+                    .send_digest(broadcast.recieved_values.as_slice())
+            },
+        );
+        self.anti_entropy = Some(id);
+    }
+
+    /// Handle `request`, sent to us by `from` if we know who that was
+    fn handle_from(
+        &mut self,
+        node: &Node,
+        request: &Body<BroadcastRequest>,
+        from: Option<&str>,
+    ) -> Option<Body<BroadcastReply>> {
+        let reply = match &request.payload {
+            BroadcastRequest::Broadcast { message } => {
+                self.receive(self.originate(message.clone()), None);
+
+                BroadcastReply::Broadcast
+            }
+            BroadcastRequest::Read { since, limit } => {
+                let since = since.unwrap_or(0).min(self.recieved_values.version());
+                let messages = self.recieved_values.since(since, *limit).to_vec();
+
+                BroadcastReply::Read {
+                    version: since + messages.len() as u64,
+                    messages,
+                }
+            }
+            BroadcastRequest::Topology { topology } => {
+                let this_node_topology = topology.get(&self.node_id).cloned().unwrap_or_default();
+                self.gossip(GossipMsg::Topology(this_node_topology));
+                self.start_anti_entropy();
+
+                BroadcastReply::Topology
+            }
+            BroadcastRequest::Gossip { values } => {
+                for value in values {
+                    self.receive(value.clone(), from);
+                }
+
+                BroadcastReply::Gossip
+            }
+            BroadcastRequest::GossipOk => {
+                if let Some(in_reply_to) = request.in_reply_to() {
+                    self.gossip(GossipMsg::GotResponse(in_reply_to));
+                }
+
+                return None;
+            }
+            BroadcastRequest::Sync { digest } => BroadcastReply::Sync {
+                messages: self
+                    .recieved_values
+                    .as_slice()
+                    .iter()
+                    .filter(|v| !digest.contains(v))
+                    .cloned()
+                    .collect(),
+            },
+            // Gossip these on like any other new value, since we'll ignore the gossip for them
+            // when it does turn up
+            BroadcastRequest::SyncOk { messages } => {
+                for m in messages {
+                    self.receive(self.originate(m.clone()), from);
+                }
+
+                return None;
+            }
+        };
+
+        Some(request.reply(node, reply))
+    }
+}
+
+impl Service for Broadcast {
+    type Request = BroadcastRequest;
+
+    fn handle(
+        &mut self,
+        node: &Node,
+        request: &Body<BroadcastRequest>,
+    ) -> Result<Option<Body<BroadcastReply>>> {
+        Ok(self.handle_from(node, request, None))
+    }
+
+    /// Gossip, and the values a sync turns up, come from peers we can then skip sending them to
+    fn handle_message(
+        &mut self,
+        node: &Node,
+        m: &Message<Body<BroadcastRequest>>,
+    ) -> Result<Option<Body<BroadcastReply>>> {
+        Ok(self.handle_from(node, &m.body, Some(&m.src)))
+    }
+
+    fn timers(&mut self) -> Option<&mut Timers<Self>> {
+        Some(&mut self.timers)
+    }
+
+    fn shutdown(&mut self, _node: &Node) -> Result<()> {
+        self.gossip_manager.flush()
+    }
+}
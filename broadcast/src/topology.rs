@@ -0,0 +1,360 @@
+use std::collections::BTreeSet;
+
+use color_eyre::eyre::{bail, Result};
+use common::{grid_topology, setting};
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+/// Decides which neighbours a node gossips to
+///
+/// Every node runs the same strategy over the same sorted `node_ids`, so computed overlays
+/// agree across the cluster without any coordination.
+pub trait TopologyStrategy: std::fmt::Debug {
+    /// Our neighbours from the moment we're initialised
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String>;
+
+    /// Our neighbours once Maelstrom sends us its `topology`. `None` keeps the current ones
+    fn on_topology(&self, _suggested: &[String]) -> Option<Vec<String>> {
+        None
+    }
+}
+
+/// Pick a strategy by name: `maelstrom`, `full-mesh`, `tree[:<fanout>]`, `hub-and-spoke`,
+/// `random[:<k>]`, `grid` or `k-regular:<k>`
+///
+/// A tree's fanout must be at least 1, and a k-regular graph's `k` a positive even number,
+/// since each node links to `k / 2` nodes on either side.
+pub fn strategy_from_name(name: &str) -> Result<Box<dyn TopologyStrategy>> {
+    let (name, arg) = match name.split_once(':') {
+        Some((name, arg)) => (name, Some(arg.parse::<usize>()?)),
+        None => (name, None),
+    };
+
+    Ok(match (name, arg) {
+        ("maelstrom", None) => Box::new(MaelstromProvided),
+        ("full-mesh", None) => Box::new(FullMesh),
+        ("tree", Some(0)) => bail!("A tree needs a fanout of at least 1"),
+        ("tree", fanout) => Box::new(Tree {
+            fanout: fanout.unwrap_or(4),
+        }),
+        ("hub-and-spoke", None) => Box::new(HubAndSpoke),
+        ("random", k) => Box::new(RandomNeighbours {
+            k: k.unwrap_or(3),
+            seed: 0,
+        }),
+        ("grid", None) => Box::new(Grid),
+        ("k-regular", Some(k)) if k == 0 || k % 2 == 1 => {
+            bail!("k-regular needs a positive even k, not {k}")
+        }
+        ("k-regular", Some(k)) => Box::new(KRegular { k }),
+        _ => bail!("Unknown overlay {name}"),
+    })
+}
+
+/// The strategy picked with `--overlay <name>`, or failing that the `BROADCAST_OVERLAY`
+/// environment variable
+pub fn configured_strategy() -> Result<Box<dyn TopologyStrategy>> {
+    let name = setting("--overlay", "BROADCAST_OVERLAY");
+
+    strategy_from_name(name.as_deref().unwrap_or("maelstrom"))
+}
+
+fn sorted(node_ids: &[String]) -> Vec<String> {
+    let mut node_ids = node_ids.to_vec();
+    node_ids.sort();
+
+    node_ids
+}
+
+fn by_index(node_ids: &[String], neighbours: impl IntoIterator<Item = usize>) -> Vec<String> {
+    neighbours
+        .into_iter()
+        .map(|i| node_ids[i].clone())
+        .collect()
+}
+
+/// Gossip straight to every other node
+#[derive(Debug)]
+pub struct FullMesh;
+
+impl TopologyStrategy for FullMesh {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        node_ids
+            .iter()
+            .filter(|id| *id != node_id)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Use the `topology` Maelstrom sends, gossiping to everyone until it arrives
+#[derive(Debug)]
+pub struct MaelstromProvided;
+
+impl TopologyStrategy for MaelstromProvided {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        FullMesh.neighbours(node_id, node_ids)
+    }
+
+    fn on_topology(&self, suggested: &[String]) -> Option<Vec<String>> {
+        Some(suggested.to_vec())
+    }
+}
+
+/// Each node is connected to its parent and `fanout` children
+#[derive(Debug)]
+pub struct Tree {
+    pub fanout: usize,
+}
+
+impl TopologyStrategy for Tree {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        let node_ids = sorted(node_ids);
+        let Some(i) = node_ids.iter().position(|id| id == node_id) else {
+            return vec![];
+        };
+
+        let fanout = self.fanout.max(1);
+        let parent = (i > 0).then(|| (i - 1) / fanout);
+        let children = (i * fanout + 1..=i * fanout + fanout).filter(|c| *c < node_ids.len());
+
+        by_index(&node_ids, parent.into_iter().chain(children))
+    }
+}
+
+/// The first node is connected to everyone, everyone else only to it
+#[derive(Debug)]
+pub struct HubAndSpoke;
+
+impl TopologyStrategy for HubAndSpoke {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        let node_ids = sorted(node_ids);
+        let Some(hub) = node_ids.first() else {
+            return vec![];
+        };
+
+        if hub == node_id {
+            FullMesh.neighbours(node_id, &node_ids)
+        } else {
+            vec![hub.clone()]
+        }
+    }
+}
+
+/// A ring, so the graph is always connected, plus `k` random links per node
+///
+/// Links go both ways, and every node draws them from the same seeded RNG, so all nodes agree
+/// on the whole graph.
+#[derive(Debug)]
+pub struct RandomNeighbours {
+    pub k: usize,
+    pub seed: u64,
+}
+
+impl TopologyStrategy for RandomNeighbours {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        let node_ids = sorted(node_ids);
+        let n = node_ids.len();
+        let Some(me) = node_ids.iter().position(|id| id == node_id) else {
+            return vec![];
+        };
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut neighbours = BTreeSet::new();
+
+        for i in 0..n {
+            let mut others: Vec<usize> = (0..n).filter(|j| *j != i).collect();
+            others.shuffle(&mut rng);
+
+            let links = others
+                .into_iter()
+                .take(self.k)
+                .chain((n > 1).then_some((i + 1) % n));
+            for j in links {
+                if i == me {
+                    neighbours.insert(j);
+                } else if j == me {
+                    neighbours.insert(i);
+                }
+            }
+        }
+        neighbours.remove(&me);
+
+        by_index(&node_ids, neighbours)
+    }
+}
+
+/// Nodes laid out in a square grid, connected to the nodes above, below and beside them, as
+/// in [`grid_topology`]
+#[derive(Debug)]
+pub struct Grid;
+
+impl TopologyStrategy for Grid {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        grid_topology(&sorted(node_ids))
+            .remove(node_id)
+            .unwrap_or_default()
+    }
+}
+
+/// A ring where each node is connected to the `k / 2` nodes on either side of it
+#[derive(Debug)]
+pub struct KRegular {
+    pub k: usize,
+}
+
+impl TopologyStrategy for KRegular {
+    fn neighbours(&self, node_id: &str, node_ids: &[String]) -> Vec<String> {
+        let node_ids = sorted(node_ids);
+        let n = node_ids.len();
+        let Some(i) = node_ids.iter().position(|id| id == node_id) else {
+            return vec![];
+        };
+
+        let neighbours: BTreeSet<usize> = (1..=(self.k / 2).max(1))
+            .flat_map(|d| [(i + d) % n, (i + n - d % n) % n])
+            .filter(|j| *j != i)
+            .collect();
+
+        by_index(&node_ids, neighbours)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use super::*;
+
+    fn ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("n{i}")).collect()
+    }
+
+    /// Every node's neighbours, as each node would work them out for itself
+    fn overlay(strategy: &dyn TopologyStrategy, n: usize) -> HashMap<String, Vec<String>> {
+        let node_ids = ids(n);
+
+        node_ids
+            .iter()
+            .map(|id| (id.clone(), strategy.neighbours(id, &node_ids)))
+            .collect()
+    }
+
+    fn assert_symmetric(overlay: &HashMap<String, Vec<String>>) {
+        for (id, neighbours) in overlay {
+            assert!(!neighbours.contains(id), "{id} is its own neighbour");
+            for neighbour in neighbours {
+                assert!(
+                    overlay[neighbour].contains(id),
+                    "{id} -> {neighbour}, but not back"
+                );
+            }
+        }
+    }
+
+    fn assert_connected(overlay: &HashMap<String, Vec<String>>) {
+        let mut reached = HashSet::from(["n0".to_owned()]);
+        let mut frontier = vec!["n0".to_owned()];
+        while let Some(id) = frontier.pop() {
+            for neighbour in &overlay[&id] {
+                if reached.insert(neighbour.clone()) {
+                    frontier.push(neighbour.clone());
+                }
+            }
+        }
+
+        assert_eq!(reached.len(), overlay.len());
+    }
+
+    #[test]
+    fn tree() {
+        let tree = Tree { fanout: 2 };
+        let node_ids = ids(7);
+
+        assert_eq!(tree.neighbours("n0", &node_ids), ["n1", "n2"]);
+        assert_eq!(tree.neighbours("n1", &node_ids), ["n0", "n3", "n4"]);
+        assert_eq!(tree.neighbours("n6", &node_ids), ["n2"]);
+
+        for n in 1..30 {
+            let overlay = overlay(&Tree { fanout: 4 }, n);
+            assert_symmetric(&overlay);
+            assert_connected(&overlay);
+        }
+    }
+
+    #[test]
+    fn random_neighbours_are_symmetric_and_connected() {
+        for n in 1..30 {
+            for seed in 0..3 {
+                let overlay = overlay(&RandomNeighbours { k: 3, seed }, n);
+
+                assert_symmetric(&overlay);
+                assert_connected(&overlay);
+                // The ring plus at least `k` of its own picks, unless there aren't that many
+                for neighbours in overlay.values() {
+                    assert!(neighbours.len() >= 3.min(n - 1));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_neighbours_depend_on_the_seed() {
+        let a = overlay(&RandomNeighbours { k: 2, seed: 0 }, 25);
+        let b = overlay(&RandomNeighbours { k: 2, seed: 1 }, 25);
+
+        assert_eq!(a, overlay(&RandomNeighbours { k: 2, seed: 0 }, 25));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn k_regular() {
+        for n in 5..30 {
+            let overlay = overlay(&KRegular { k: 4 }, n);
+
+            assert_symmetric(&overlay);
+            assert_connected(&overlay);
+            for neighbours in overlay.values() {
+                assert_eq!(neighbours.len(), 4);
+            }
+        }
+
+        // Too few nodes for every node to have `k` distinct neighbours
+        let overlay = overlay(&KRegular { k: 4 }, 3);
+        assert_eq!(overlay["n0"], ["n1", "n2"]);
+    }
+
+    #[test]
+    fn strategies_sort_node_ids_first() {
+        let node_ids = ids(12);
+        let mut shuffled = node_ids.clone();
+        shuffled.reverse();
+
+        for strategy in ["tree", "hub-and-spoke", "random", "grid", "k-regular:4"] {
+            let strategy = strategy_from_name(strategy).unwrap();
+
+            for id in &node_ids {
+                assert_eq!(
+                    strategy.neighbours(id, &node_ids),
+                    strategy.neighbours(id, &shuffled),
+                    "{strategy:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_strategies() {
+        assert!(strategy_from_name("ring").is_err());
+        assert!(strategy_from_name("k-regular").is_err());
+        assert!(strategy_from_name("tree:many").is_err());
+    }
+
+    #[test]
+    fn strategies_that_cant_be_built_as_asked() {
+        assert!(strategy_from_name("tree:0").is_err());
+        assert!(strategy_from_name("k-regular:0").is_err());
+        assert!(strategy_from_name("k-regular:3").is_err());
+        assert!(strategy_from_name("tree:1").is_ok());
+        assert!(strategy_from_name("k-regular:2").is_ok());
+    }
+}
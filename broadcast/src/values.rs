@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use serde_json::{Number, Value};
+
+/// Every value this node has received, in the order it first received them
+///
+/// A value's position in that order is its version, so `read_ok` output is stable, and a
+/// client can ask for only the values since the last version it read.
+#[derive(Debug, Default)]
+pub struct Values {
+    seen: HashSet<Value>,
+    log: Vec<Value>,
+}
+
+impl Values {
+    /// Returns false if we already had `value`
+    pub fn insert(&mut self, value: Value) -> bool {
+        if !self.seen.insert(value.clone()) {
+            return false;
+        }
+        self.log.push(value);
+
+        true
+    }
+
+    pub fn contains(&self, value: &Value) -> bool {
+        self.seen.contains(value)
+    }
+
+    /// The version a read of everything we have right now is at
+    pub fn version(&self) -> u64 {
+        self.log.len() as u64
+    }
+
+    /// Up to `limit` values received after `version`
+    pub fn since(&self, version: u64, limit: Option<usize>) -> &[Value] {
+        let start = (version as usize).min(self.log.len());
+        let end = limit.map_or(self.log.len(), |limit| {
+            start.saturating_add(limit).min(self.log.len())
+        });
+
+        &self.log[start..end]
+    }
+
+    pub fn as_slice(&self) -> &[Value] {
+        &self.log
+    }
+}
+
+/// `value` with every whole float written as an integer, so `1.0` and `1` are the same value
+///
+/// Objects need nothing done, since their keys are kept sorted and compare regardless of order.
+pub fn canonical(value: &Value) -> Value {
+    match value {
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() && f.fract() == 0.0 => {
+                if f >= 0.0 && f < u64::MAX as f64 {
+                    Value::Number(Number::from(f as u64))
+                } else if f < 0.0 && f >= i64::MIN as f64 {
+                    Value::Number(Number::from(f as i64))
+                } else {
+                    value.clone()
+                }
+            }
+            _ => value.clone(),
+        },
+        Value::Array(values) => Value::Array(values.iter().map(canonical).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| (key.clone(), canonical(value)))
+                .collect(),
+        ),
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn whole_floats_become_integers() {
+        assert_eq!(canonical(&json!(1.0)), json!(1));
+        assert_eq!(canonical(&json!(-3.0)), json!(-3));
+        assert_eq!(canonical(&json!(1.5)), json!(1.5));
+        assert_eq!(canonical(&json!(1e300)), json!(1e300));
+    }
+
+    #[test]
+    fn nested_values_are_canonical_too() {
+        assert_eq!(
+            canonical(&json!({"a": [1.0, {"b": 2.0}], "c": "1.0"})),
+            json!({"a": [1, {"b": 2}], "c": "1.0"})
+        );
+    }
+
+    #[test]
+    fn canonical_values_are_deduplicated() {
+        let mut values = Values::default();
+
+        assert!(values.insert(canonical(&json!(1))));
+        assert!(!values.insert(canonical(&json!(1.0))));
+        assert_eq!(values.as_slice(), [json!(1)]);
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use broadcast::canonical;
+use serde_json::Value;
+
+use crate::{CheckResult, History, Op};
+
+/// Values are compared by their JSON text so any kind of message can be checked, once made
+/// canonical the way nodes store them, so a `1.0` broadcast matches the `1` read back
+fn key(value: &Value) -> String {
+    canonical(value).to_string()
+}
+
+/// Every value whose `broadcast` was acknowledged must show up in each node's last `read_ok`,
+/// and reads may only contain values some client actually broadcast
+pub fn check(history: &History, ops: &[Op]) -> CheckResult {
+    let broadcasts: Vec<_> = ops
+        .iter()
+        .filter(|op| op.op_type() == "broadcast")
+        .collect();
+    let mut errors = vec![];
+
+    let attempted: HashSet<String> = broadcasts
+        .iter()
+        .map(|op| key(&op.request.message.body["message"]))
+        .collect();
+    let acknowledged: HashSet<String> = broadcasts
+        .iter()
+        .filter(|op| op.ok_body().is_some())
+        .map(|op| key(&op.request.message.body["message"]))
+        .collect();
+
+    for node_id in history.node_ids() {
+        let final_read = ops
+            .iter()
+            .filter(|op| op.op_type() == "read" && op.request.message.dest == node_id)
+            .filter_map(|op| op.ok_body().zip(op.reply))
+            // Without timings this is the last read in the history, which is in send order
+            .max_by_key(|(_, reply)| reply.time);
+
+        let Some((body, _)) = final_read else {
+            errors.push(format!("{node_id} never answered a read"));
+            continue;
+        };
+
+        let read: HashSet<String> = body["messages"]
+            .as_array()
+            .map(|messages| messages.iter().map(key).collect())
+            .unwrap_or_default();
+
+        let mut lost: Vec<_> = acknowledged.difference(&read).collect();
+        if !lost.is_empty() {
+            lost.sort();
+            errors.push(format!(
+                "{node_id} lost {} acknowledged values: {lost:?}",
+                lost.len()
+            ));
+        }
+
+        let mut unexpected: Vec<_> = read.difference(&attempted).collect();
+        if !unexpected.is_empty() {
+            unexpected.sort();
+            errors.push(format!(
+                "{node_id} read values nobody broadcast: {unexpected:?}"
+            ));
+        }
+    }
+
+    CheckResult {
+        workload: "broadcast",
+        ops: broadcasts.len(),
+        errors,
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{CheckResult, Op};
+
+/// Every `echo` that got a reply must have been answered with an `echo_ok` carrying the same
+/// `echo` back
+pub fn check(ops: &[Op]) -> CheckResult {
+    let ops: Vec<_> = ops.iter().filter(|op| op.op_type() == "echo").collect();
+    let mut errors = vec![];
+
+    for op in &ops {
+        let Some(reply) = op.reply else { continue };
+
+        let request = &op.request.message.body;
+        let reply = &reply.message.body;
+
+        if reply["type"] != "echo_ok" {
+            errors.push(format!("{request} was answered with {reply}"));
+        } else if reply["echo"] != request["echo"] {
+            errors.push(format!("{request} was echoed as {}", reply["echo"]));
+        }
+    }
+
+    CheckResult {
+        workload: "echo",
+        ops: ops.len(),
+        errors,
+    }
+}
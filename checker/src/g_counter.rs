@@ -0,0 +1,57 @@
+use crate::{CheckResult, History, Op};
+
+/// Each node's last `read_ok` must count every acknowledged `add`, and nothing that was never
+/// added. Adds that got no reply may or may not have happened, so with every add acknowledged
+/// the read is exactly their sum
+pub fn check(history: &History, ops: &[Op]) -> CheckResult {
+    let adds: Vec<_> = ops.iter().filter(|op| op.op_type() == "add").collect();
+    let mut errors = vec![];
+
+    let delta = |op: &&Op| {
+        op.request.message.body["delta"]
+            .as_u64()
+            .unwrap_or_default()
+    };
+    let attempted: u64 = adds.iter().map(delta).sum();
+    let acknowledged: u64 = adds
+        .iter()
+        .filter(|op| op.ok_body().is_some())
+        .map(delta)
+        .sum();
+
+    for node_id in history.node_ids() {
+        let final_read = ops
+            .iter()
+            .filter(|op| op.op_type() == "read" && op.request.message.dest == node_id)
+            .filter_map(|op| op.ok_body().zip(op.reply))
+            // Without timings this is the last read in the history, which is in send order
+            .max_by_key(|(_, reply)| reply.time);
+
+        let Some((body, _)) = final_read else {
+            errors.push(format!("{node_id} never answered a read"));
+            continue;
+        };
+
+        let Some(value) = body["value"].as_u64() else {
+            errors.push(format!("{node_id} read {}", body["value"]));
+            continue;
+        };
+
+        if value < acknowledged {
+            errors.push(format!(
+                "{node_id} read {value}, but acknowledged adds sum to {acknowledged}"
+            ));
+        }
+        if value > attempted {
+            errors.push(format!(
+                "{node_id} read {value}, but only {attempted} was ever added"
+            ));
+        }
+    }
+
+    CheckResult {
+        workload: "g-counter",
+        ops: adds.len(),
+        errors,
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{CheckResult, Op};
+
+/// An acknowledged `send`
+struct Sent<'a> {
+    op: &'a Op<'a>,
+    msg: &'a Value,
+    offset: u64,
+}
+
+impl Sent<'_> {
+    /// Whether this send was acked before `op` was sent. Never true without timings
+    fn acked_before(&self, op: &Op) -> bool {
+        let acked = self.op.reply.and_then(|reply| reply.time);
+
+        acked.zip(op.request.time).is_some_and(|(a, b)| a < b)
+    }
+}
+
+/// Each key's offsets are handed out once, and a send acked before another starts gets the
+/// lower offset. Every message a `poll_ok` returns was sent to its key at that offset, and a
+/// poll returns every send acked before it at or after the offset it asked for
+pub fn check(ops: &[Op]) -> CheckResult {
+    let sends: Vec<_> = ops.iter().filter(|op| op.op_type() == "send").collect();
+    let mut errors = vec![];
+
+    let mut attempted: HashMap<&str, Vec<&Value>> = HashMap::new();
+    let mut acked: HashMap<&str, Vec<Sent>> = HashMap::new();
+    for op in &sends {
+        let body = &op.request.message.body;
+        let (Some(key), msg) = (body["key"].as_str(), &body["msg"]) else {
+            errors.push(format!("send without a key: {body}"));
+            continue;
+        };
+        attempted.entry(key).or_default().push(msg);
+
+        let Some(reply) = op.ok_body() else { continue };
+        let Some(offset) = reply["offset"].as_u64() else {
+            errors.push(format!("send was answered with {reply}"));
+            continue;
+        };
+        acked.entry(key).or_default().push(Sent { op, msg, offset });
+    }
+
+    for (key, sent) in &acked {
+        for (i, a) in sent.iter().enumerate() {
+            for b in &sent[i + 1..] {
+                if a.offset == b.offset && a.msg != b.msg {
+                    errors.push(format!(
+                        "{key} offset {} was handed out for both {} and {}",
+                        a.offset, a.msg, b.msg
+                    ));
+                }
+            }
+
+            for b in sent {
+                if a.acked_before(b.op) && a.offset > b.offset {
+                    errors.push(format!(
+                        "{} sent to {key} got offset {}, though {} was acked before it at {}",
+                        b.msg, b.offset, a.msg, a.offset
+                    ));
+                }
+            }
+        }
+    }
+
+    for op in ops.iter().filter(|op| op.op_type() == "poll") {
+        let Some(reply) = op.ok_body() else { continue };
+        let Some(offsets) = op.request.message.body["offsets"].as_object() else {
+            continue;
+        };
+
+        for (key, from) in offsets {
+            let from = from.as_u64().unwrap_or_default();
+            let polled: HashMap<u64, &Value> = reply["msgs"][key]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|entry| Some((entry[0].as_u64()?, &entry[1])))
+                .collect();
+            let sent = acked
+                .get(key.as_str())
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+
+            for (offset, msg) in &polled {
+                let expected = sent.iter().find(|s| s.offset == *offset);
+                let ok = match expected {
+                    Some(expected) => expected.msg == *msg,
+                    None => attempted
+                        .get(key.as_str())
+                        .is_some_and(|msgs| msgs.contains(msg)),
+                };
+
+                if !ok {
+                    errors.push(format!(
+                        "poll of {key} returned {msg} at {offset}, which wasn't sent there"
+                    ));
+                }
+            }
+
+            for s in sent {
+                if s.offset >= from && s.acked_before(op) && !polled.contains_key(&s.offset) {
+                    errors.push(format!(
+                        "poll of {key} from {from} missed {} at {}, acked before it",
+                        s.msg, s.offset
+                    ));
+                }
+            }
+        }
+    }
+
+    errors.sort();
+    errors.dedup();
+
+    CheckResult {
+        workload: "kafka",
+        ops: sends.len(),
+        errors,
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    time::Duration,
+};
+
+use color_eyre::eyre::Result;
+use serde_json::Value;
+pub use simulator::Event;
+
+mod broadcast;
+mod echo;
+mod g_counter;
+mod kafka;
+mod stats;
+mod unique_ids;
+
+pub use stats::*;
+
+/// A recorded run: every message put on the network, one [`Event`] per JSONL line
+pub struct History {
+    pub events: Vec<Event>,
+}
+
+/// A client request along with the reply it got, if any
+pub struct Op<'a> {
+    pub request: &'a Event,
+    pub reply: Option<&'a Event>,
+}
+
+impl Op<'_> {
+    pub fn op_type(&self) -> &str {
+        self.request.message.body["type"]
+            .as_str()
+            .unwrap_or_default()
+    }
+
+    /// The reply body, if the op got a reply that isn't an `error`
+    pub fn ok_body(&self) -> Option<&Value> {
+        self.reply
+            .map(|reply| &reply.message.body)
+            .filter(|body| body["type"] != "error")
+    }
+
+    /// `None` without a reply, or if the history has no timings
+    pub fn latency(&self) -> Option<Duration> {
+        let sent = self.request.time?;
+        let replied = self.reply?.time?;
+
+        Some(Duration::from_micros(replied.saturating_sub(sent)))
+    }
+}
+
+impl History {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_reader(std::fs::File::open(path)?)
+    }
+
+    pub fn from_reader(reader: impl Read) -> Result<Self> {
+        let mut events = vec![];
+
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            events.push(serde_json::from_str(&line)?);
+        }
+
+        Ok(Self { events })
+    }
+
+    /// The node ids handed out in the `init` messages
+    pub fn node_ids(&self) -> Vec<String> {
+        let mut node_ids: Vec<String> = self
+            .events
+            .iter()
+            .filter(|e| e.message.body["type"] == "init")
+            .map(|e| e.message.dest.clone())
+            .collect();
+        node_ids.sort();
+        node_ids.dedup();
+
+        node_ids
+    }
+
+    fn is_node(&self, node_ids: &[String], id: &str) -> bool {
+        node_ids.iter().any(|n| n == id)
+    }
+
+    /// Every request a client sent to a node, paired with the node's reply
+    pub fn client_ops(&self) -> Vec<Op<'_>> {
+        let node_ids = self.node_ids();
+
+        let mut replies: HashMap<(&str, &str, u64), &Event> = HashMap::new();
+        for e in &self.events {
+            let m = &e.message;
+            if let Some(in_reply_to) = m.body["in_reply_to"].as_u64() {
+                replies.insert((&m.dest, &m.src, in_reply_to), e);
+            }
+        }
+
+        self.events
+            .iter()
+            .filter(|e| {
+                let m = &e.message;
+                !self.is_node(&node_ids, &m.src)
+                    && self.is_node(&node_ids, &m.dest)
+                    && m.body["in_reply_to"].is_null()
+            })
+            .map(|request| {
+                let m = &request.message;
+                let reply = m
+                    .body
+                    .get("msg_id")
+                    .and_then(Value::as_u64)
+                    .and_then(|msg_id| replies.get(&(&m.src, &m.dest, msg_id)).copied());
+
+                Op { request, reply }
+            })
+            .collect()
+    }
+
+    /// Messages sent from one node to another
+    pub fn server_messages(&self) -> usize {
+        let node_ids = self.node_ids();
+
+        self.events
+            .iter()
+            .filter(|e| {
+                self.is_node(&node_ids, &e.message.src) && self.is_node(&node_ids, &e.message.dest)
+            })
+            .count()
+    }
+}
+
+/// The outcome of checking one workload
+#[derive(Debug)]
+pub struct CheckResult {
+    pub workload: &'static str,
+    pub ops: usize,
+    pub errors: Vec<String>,
+}
+
+impl CheckResult {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// Run the checker for every workload that shows up in `history`
+pub fn check(history: &History) -> Vec<CheckResult> {
+    let ops = history.client_ops();
+    let has_op = |op_type: &str| ops.iter().any(|op| op.op_type() == op_type);
+
+    let mut results = vec![];
+    if has_op("echo") {
+        results.push(echo::check(&ops));
+    }
+    if has_op("generate") {
+        results.push(unique_ids::check(&ops));
+    }
+    if has_op("broadcast") {
+        results.push(broadcast::check(history, &ops));
+    }
+    if has_op("add") {
+        results.push(g_counter::check(history, &ops));
+    }
+    if has_op("send") {
+        results.push(kafka::check(&ops));
+    }
+
+    results
+}
+
+/// The checks for a recorded run along with its network and latency statistics
+pub struct Report {
+    pub results: Vec<CheckResult>,
+    pub stats: Stats,
+    pub target_failures: Vec<String>,
+}
+
+impl Report {
+    pub fn new(history: &History, targets: &Targets) -> Self {
+        let stats = Stats::new(history);
+        let target_failures = targets.failures(&stats);
+
+        Self {
+            results: check(history),
+            stats,
+            target_failures,
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.results.iter().all(CheckResult::is_valid) && self.target_failures.is_empty()
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for result in &self.results {
+            let verdict = if result.is_valid() {
+                "valid"
+            } else {
+                "INVALID"
+            };
+            writeln!(f, "{}: {verdict} ({} ops)", result.workload, result.ops)?;
+
+            for error in &result.errors {
+                writeln!(f, "  {error}")?;
+            }
+        }
+
+        write!(f, "{}", self.stats)?;
+
+        for failure in &self.target_failures {
+            writeln!(f, "Missed target: {failure}")?;
+        }
+
+        let verdict = if self.is_valid() { "valid" } else { "INVALID" };
+        writeln!(f, "Everything looks {verdict}")
+    }
+}
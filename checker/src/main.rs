@@ -0,0 +1,36 @@
+use std::{path::PathBuf, time::Duration};
+
+use checker::{History, Report, Targets};
+use color_eyre::eyre::{bail, eyre, Result};
+
+fn main() -> Result<()> {
+    let mut path = None;
+    let mut targets = Targets::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| eyre!("{arg} needs a value"));
+
+        match arg.as_str() {
+            "--max-msgs-per-op" => targets.max_msgs_per_op = Some(value()?.parse()?),
+            "--max-median-latency" => {
+                targets.max_median_latency = Some(Duration::from_millis(value()?.parse()?))
+            }
+            "--max-latency" => targets.max_latency = Some(Duration::from_millis(value()?.parse()?)),
+            flag if flag.starts_with("--") => bail!("Unknown flag {flag}"),
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let path = path.ok_or_else(|| eyre!("Usage: checker <history.jsonl> [--max-msgs-per-op N] [--max-median-latency MS] [--max-latency MS]"))?;
+    let history = History::load(&path)?;
+
+    let report = Report::new(&history, &targets);
+    print!("{report}");
+
+    if !report.is_valid() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
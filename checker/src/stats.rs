@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::History;
+
+/// Client ops that only set the nodes up, and don't count towards the workload's stats
+const SETUP_OPS: [&str; 2] = ["init", "topology"];
+
+#[derive(Debug)]
+pub struct Stats {
+    pub ops: usize,
+    pub server_messages: usize,
+    pub msgs_per_op: f64,
+    pub median_latency: Option<Duration>,
+    pub max_latency: Option<Duration>,
+}
+
+impl Stats {
+    pub fn new(history: &History) -> Self {
+        let ops: Vec<_> = history
+            .client_ops()
+            .into_iter()
+            .filter(|op| !SETUP_OPS.contains(&op.op_type()))
+            .collect();
+
+        let mut latencies: Vec<Duration> = ops.iter().filter_map(|op| op.latency()).collect();
+        latencies.sort();
+
+        let server_messages = history.server_messages();
+        let msgs_per_op = if ops.is_empty() {
+            0.0
+        } else {
+            server_messages as f64 / ops.len() as f64
+        };
+
+        Self {
+            ops: ops.len(),
+            server_messages,
+            msgs_per_op,
+            median_latency: latencies.get(latencies.len() / 2).copied(),
+            max_latency: latencies.last().copied(),
+        }
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Ops: {}", self.ops)?;
+        writeln!(f, "Server messages: {}", self.server_messages)?;
+        writeln!(f, "Messages per op: {:.2}", self.msgs_per_op)?;
+
+        if let (Some(median), Some(max)) = (self.median_latency, self.max_latency) {
+            writeln!(f, "Median latency: {}ms", median.as_millis())?;
+            writeln!(f, "Max latency: {}ms", max.as_millis())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Performance targets, like the ones at the top of `broadcast/run_e.sh`
+#[derive(Debug, Default, Clone)]
+pub struct Targets {
+    pub max_msgs_per_op: Option<f64>,
+    pub max_median_latency: Option<Duration>,
+    pub max_latency: Option<Duration>,
+}
+
+impl Targets {
+    pub fn failures(&self, stats: &Stats) -> Vec<String> {
+        let mut failures = vec![];
+
+        if let Some(target) = self.max_msgs_per_op {
+            if stats.msgs_per_op >= target {
+                failures.push(format!(
+                    "{:.2} messages per op is not below {target}",
+                    stats.msgs_per_op
+                ));
+            }
+        }
+
+        let latency_targets = [
+            ("Median", self.max_median_latency, stats.median_latency),
+            ("Max", self.max_latency, stats.max_latency),
+        ];
+        for (name, target, actual) in latency_targets {
+            let Some(target) = target else { continue };
+
+            match actual {
+                Some(actual) if actual < target => {}
+                Some(actual) => failures.push(format!(
+                    "{name} latency of {}ms is not below {}ms",
+                    actual.as_millis(),
+                    target.as_millis()
+                )),
+                None => failures.push(format!("{name} latency unknown, no op got a reply")),
+            }
+        }
+
+        failures
+    }
+}
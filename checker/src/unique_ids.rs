@@ -0,0 +1,35 @@
+use std::collections::HashMap;
+
+use crate::{CheckResult, Op};
+
+/// No two `generate_ok` replies may carry the same id, no matter which node generated them
+pub fn check(ops: &[Op]) -> CheckResult {
+    let ops: Vec<_> = ops.iter().filter(|op| op.op_type() == "generate").collect();
+    let mut errors = vec![];
+    let mut seen: HashMap<String, &str> = HashMap::new();
+
+    for op in &ops {
+        let Some(body) = op.ok_body() else { continue };
+
+        if body["type"] != "generate_ok" {
+            errors.push(format!("generate was answered with {body}"));
+            continue;
+        }
+
+        let node = op.request.message.dest.as_str();
+        // serde_json's Value serializes maps in a stable order, so this is canonical
+        let id = body["id"].to_string();
+
+        if let Some(first) = seen.insert(id.clone(), node) {
+            errors.push(format!(
+                "{node} generated {id}, which {first} already handed out"
+            ));
+        }
+    }
+
+    CheckResult {
+        workload: "unique-ids",
+        ops: ops.len(),
+        errors,
+    }
+}
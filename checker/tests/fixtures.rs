@@ -0,0 +1,128 @@
+//! The checker run over small hand-written histories in `tests/fixtures`
+
+use std::time::Duration;
+
+use checker::{History, Report, Targets};
+
+fn load(fixture: &str) -> History {
+    History::load(format!(
+        "{}/tests/fixtures/{fixture}",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+fn report(fixture: &str) -> Report {
+    Report::new(&load(fixture), &Targets::default())
+}
+
+/// The errors for `workload`, having checked every other workload is valid
+fn errors(report: &Report, workload: &str) -> Vec<String> {
+    for result in &report.results {
+        if result.workload != workload {
+            assert!(result.is_valid(), "{report}");
+        }
+    }
+
+    report
+        .results
+        .iter()
+        .find(|result| result.workload == workload)
+        .unwrap()
+        .errors
+        .clone()
+}
+
+#[test]
+fn valid_history() {
+    let report = report("valid.jsonl");
+
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(report.results.len(), 3);
+    assert_eq!(report.stats.ops, 6);
+    assert_eq!(report.stats.server_messages, 2);
+    assert_eq!(report.stats.median_latency, Some(Duration::from_micros(50)));
+}
+
+#[test]
+fn history_without_timings() {
+    let report = report("untimed.jsonl");
+
+    assert!(report.is_valid(), "{report}");
+    assert_eq!(report.stats.ops, 6);
+    assert_eq!(report.stats.median_latency, None);
+    assert!(!report.to_string().contains("latency"));
+}
+
+#[test]
+fn latency_targets_fail_without_timings() {
+    let history = load("untimed.jsonl");
+    let targets = Targets {
+        max_latency: Some(Duration::from_secs(1)),
+        ..Targets::default()
+    };
+
+    assert!(!Report::new(&history, &targets).is_valid());
+}
+
+#[test]
+fn lost_acknowledged_broadcast() {
+    let report = report("lost_broadcast.jsonl");
+
+    assert!(!report.is_valid());
+    assert_eq!(
+        errors(&report, "broadcast"),
+        [r#"n2 lost 1 acknowledged values: ["7"]"#]
+    );
+}
+
+#[test]
+fn whole_number_floats_are_read_back_as_integers() {
+    let report = report("whole_float_broadcast.jsonl");
+
+    assert!(report.is_valid(), "{report}");
+}
+
+#[test]
+fn duplicate_generated_id() {
+    let report = report("duplicate_id.jsonl");
+
+    assert!(!report.is_valid());
+    assert_eq!(
+        errors(&report, "unique-ids"),
+        [r#"n2 generated ["n1",0], which n1 already handed out"#]
+    );
+}
+
+#[test]
+fn g_counter_missing_an_acknowledged_add() {
+    let report = report("g_counter_lost_add.jsonl");
+
+    assert!(!report.is_valid());
+    assert_eq!(
+        errors(&report, "g-counter"),
+        ["n2 read 4, but acknowledged adds sum to 7"]
+    );
+}
+
+#[test]
+fn kafka_offset_handed_out_twice() {
+    let report = report("kafka_reused_offset.jsonl");
+
+    assert!(!report.is_valid());
+    assert_eq!(
+        errors(&report, "kafka"),
+        ["k offset 0 was handed out for both 10 and 20"]
+    );
+}
+
+#[test]
+fn echo_mismatch() {
+    let report = report("echo_mismatch.jsonl");
+
+    assert!(!report.is_valid());
+    assert_eq!(
+        errors(&report, "echo"),
+        [r#"{"echo":"hello","msg_id":1,"type":"echo"} was echoed as "goodbye""#]
+    );
+}
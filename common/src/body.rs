@@ -0,0 +1,382 @@
+use serde::{de::DeserializeOwned, de::Error, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{MsgId, MsgIdAble};
+
+/// The standard Maelstrom body: the ids every message may carry, with its payload's fields
+/// (including its `type`) flattened in alongside them
+///
+/// `Body<Value>` reads any message, so the ids and type can be looked at without knowing
+/// which enum its payload belongs to.
+#[derive(Serialize, Debug, Clone)]
+pub struct Body<P> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub msg_id: Option<MsgId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<MsgId>,
+    #[serde(flatten)]
+    pub payload: P,
+    /// Fields the payload doesn't know about, kept so they survive being passed on
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// serde would hand a flattened `extra` every field, including the payload's, so this works
+/// out which ones the payload didn't take
+///
+/// Those are the fields [`Payload::fields`] doesn't list for the payload's `type`. A payload
+/// that doesn't list its fields, like `Value`, takes them all and leaves `extra` empty.
+impl<'de, P: Payload + DeserializeOwned> Deserialize<'de> for Body<P> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut fields = Map::<String, Value>::deserialize(deserializer)?;
+
+        let mut id = |key: &str| match fields.remove(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(id) => serde_json::from_value(id)
+                .map(Some)
+                .map_err(D::Error::custom),
+        };
+        let msg_id = id("msg_id")?;
+        let in_reply_to = id("in_reply_to")?;
+
+        let payload: P =
+            serde_json::from_value(Value::Object(fields.clone())).map_err(D::Error::custom)?;
+
+        let extra = match P::fields(payload.kind()) {
+            Some(known) => fields
+                .into_iter()
+                .filter(|(key, _)| key != "type" && !known.contains(&key.as_str()))
+                .collect(),
+            None => Map::new(),
+        };
+
+        Ok(Self {
+            msg_id,
+            in_reply_to,
+            payload,
+            extra,
+        })
+    }
+}
+
+/// A body type that may keep fields it doesn't know about, which replies echo when
+/// [`crate::Handler::echo_extra`] is on
+///
+/// Only [`Body`] keeps them. Other body types drop unknown fields as they're read, and
+/// implement this with the default.
+pub trait ExtraFields {
+    fn extra_fields(&self) -> Map<String, Value> {
+        Map::new()
+    }
+}
+
+impl<P> ExtraFields for Body<P> {
+    fn extra_fields(&self) -> Map<String, Value> {
+        self.extra.clone()
+    }
+}
+
+impl ExtraFields for Value {}
+
+/// A message payload, which knows the `type` it's sent as
+///
+/// `#[request]` and `#[reply]` implement this.
+pub trait Payload {
+    fn kind(&self) -> &str;
+
+    /// Every name a payload of type `kind` reads a field by, aliases included, so the rest can
+    /// be kept as unknown. `None` if it may read any field
+    fn fields(_kind: &str) -> Option<&'static [&'static str]>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+impl Payload for Value {
+    fn kind(&self) -> &str {
+        self["type"].as_str().unwrap_or_default()
+    }
+}
+
+/// Every `type` a payload enum can be sent as
+///
+/// `#[request]` and `#[reply]` implement this.
+pub trait Kinds {
+    const KINDS: &'static [&'static str];
+}
+
+/// A request payload and the payload of its replies, as declared by
+/// `#[request(reply = ...)]`
+pub trait Paired {
+    type Reply;
+}
+
+impl<P> Body<P> {
+    pub fn new(payload: P) -> Self {
+        Self {
+            msg_id: None,
+            in_reply_to: None,
+            payload,
+            extra: Map::new(),
+        }
+    }
+
+    pub fn with_extra(self, extra: Map<String, Value>) -> Self {
+        Self { extra, ..self }
+    }
+
+    pub fn msg_id(&self) -> Option<MsgId> {
+        self.msg_id
+    }
+
+    pub fn in_reply_to(&self) -> Option<MsgId> {
+        self.in_reply_to
+    }
+
+    pub fn is_reply(&self) -> bool {
+        self.in_reply_to.is_some()
+    }
+
+    pub fn payload(&self) -> &P {
+        &self.payload
+    }
+
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+}
+
+impl<P: Payload> Body<P> {
+    pub fn kind(&self) -> &str {
+        self.payload.kind()
+    }
+}
+
+impl<P: Paired> Body<P> {
+    /// Our reply to this message, with a fresh `msg_id` from `ids`
+    ///
+    /// A message without a `msg_id` isn't waiting on a reply, but gets one without an
+    /// `in_reply_to` all the same. Its unknown fields aren't echoed unless asked for, with
+    /// [`crate::Handler::echo_extra`], [`crate::Router::with_echo_extra`] or
+    /// `.with_extra(request.extra.clone())`.
+    pub fn reply(&self, ids: &impl MsgIdAble, payload: P::Reply) -> Body<P::Reply> {
+        Body {
+            msg_id: Some(ids.generate_msg_id()),
+            in_reply_to: self.msg_id,
+            payload,
+            extra: Map::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::Result;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{Handler, Message, NodeIdable};
+
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    enum Request {
+        Echo {
+            echo: String,
+        },
+        Read {
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            since: Option<u64>,
+            #[serde(alias = "max")]
+            limit: u64,
+            #[serde(skip_deserializing)]
+            cursor: u64,
+        },
+    }
+
+    /// What `#[request]` would generate
+    impl Payload for Request {
+        fn kind(&self) -> &str {
+            match self {
+                Request::Echo { .. } => "echo",
+                Request::Read { .. } => "read",
+            }
+        }
+
+        fn fields(kind: &str) -> Option<&'static [&'static str]> {
+            match kind {
+                "echo" => Some(&["echo"]),
+                "read" => Some(&["since", "limit", "max"]),
+                _ => None,
+            }
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    enum Reply {
+        EchoOk { echo: String },
+    }
+
+    fn body(value: Value) -> Body<Request> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let body = body(json!({"type": "echo", "msg_id": 1, "echo": "hi", "trace": "abc"}));
+
+        assert_eq!(body.msg_id, Some(1));
+        assert_eq!(body.payload, Request::Echo { echo: "hi".into() });
+        assert_eq!(
+            body.extra_fields(),
+            json!({"trace": "abc"}).as_object().cloned().unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&body).unwrap(),
+            json!({"type": "echo", "msg_id": 1, "echo": "hi", "trace": "abc"})
+        );
+    }
+
+    #[test]
+    fn fields_that_dont_serialize_back_are_still_known() {
+        let body = body(json!({"type": "read", "since": null, "limit": 3}));
+
+        assert_eq!(
+            body.payload,
+            Request::Read {
+                since: None,
+                limit: 3,
+                cursor: 0,
+            }
+        );
+        assert!(body.extra.is_empty());
+    }
+
+    #[test]
+    fn aliased_fields_are_known() {
+        let body = body(json!({"type": "read", "max": 3}));
+
+        assert_eq!(
+            body.payload,
+            Request::Read {
+                since: None,
+                limit: 3,
+                cursor: 0,
+            }
+        );
+        assert!(body.extra.is_empty());
+    }
+
+    #[test]
+    fn fields_serde_skips_are_extra() {
+        let body = body(json!({"type": "read", "limit": 3, "cursor": 9}));
+
+        assert_eq!(
+            body.extra,
+            json!({"cursor": 9}).as_object().cloned().unwrap()
+        );
+    }
+
+    #[test]
+    fn payloads_that_dont_list_their_fields_take_them_all() {
+        let body: Body<Value> =
+            serde_json::from_value(json!({"type": "echo", "msg_id": 1, "trace": "abc"})).unwrap();
+
+        assert!(body.extra.is_empty());
+        assert_eq!(body.payload, json!({"type": "echo", "trace": "abc"}));
+    }
+
+    /// Replies to everything with an `echo_ok`, keeping what it sends
+    struct Echo {
+        echo_extra: bool,
+        sent: Vec<String>,
+    }
+
+    impl NodeIdable for Echo {
+        fn node_id(&self) -> &str {
+            "n0"
+        }
+    }
+
+    impl Handler for Echo {
+        type RequestBody = Body<Request>;
+        type ResponseBody = Body<Reply>;
+
+        fn try_handle_request(&mut self, request: &Body<Request>) -> Result<Option<Body<Reply>>> {
+            let echo = match &request.payload {
+                Request::Echo { echo } => echo.clone(),
+                Request::Read { .. } => "read".to_owned(),
+            };
+
+            Ok(Some(Body {
+                in_reply_to: request.msg_id,
+                ..Body::new(Reply::EchoOk { echo })
+            }))
+        }
+
+        fn echo_extra(&self) -> bool {
+            self.echo_extra
+        }
+
+        fn send_message<B: Serialize + Clone>(&mut self, m: Message<B>) -> Result<()> {
+            self.sent.push(serde_json::to_string(&m)?);
+
+            Ok(())
+        }
+    }
+
+    fn reply(echo_extra: bool, request: Value) -> Value {
+        let mut handler = Echo {
+            echo_extra,
+            sent: vec![],
+        };
+        handler.handle_line(&request.to_string()).unwrap();
+
+        assert_eq!(handler.sent.len(), 1);
+        serde_json::from_str(&handler.sent[0]).unwrap()
+    }
+
+    #[test]
+    fn replies_echo_unknown_fields_when_asked() {
+        let request = json!({
+            "src": "c1",
+            "dest": "n0",
+            "hop": 2,
+            "body": {"type": "echo", "msg_id": 1, "echo": "hi", "trace": "abc"},
+        });
+
+        assert_eq!(
+            reply(true, request.clone()),
+            json!({
+                "src": "n0",
+                "dest": "c1",
+                "hop": 2,
+                "body": {"type": "echo_ok", "in_reply_to": 1, "echo": "hi", "trace": "abc"},
+            })
+        );
+        assert_eq!(
+            reply(false, request),
+            json!({
+                "src": "n0",
+                "dest": "c1",
+                "body": {"type": "echo_ok", "in_reply_to": 1, "echo": "hi"},
+            })
+        );
+    }
+
+    #[test]
+    fn echoed_fields_never_replace_the_replys_own() {
+        let request = json!({
+            "src": "c1",
+            "dest": "n0",
+            "body": {"type": "read", "msg_id": 1, "limit": 3, "echo": "mine"},
+        });
+
+        assert_eq!(
+            reply(true, request)["body"],
+            json!({"type": "echo_ok", "in_reply_to": 1, "echo": "read"})
+        );
+    }
+}
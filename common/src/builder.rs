@@ -0,0 +1,73 @@
+use std::io;
+
+use color_eyre::eyre::{bail, Result};
+use serde_json::Value;
+
+use crate::{read_stdin, Body, EventHandler, Handler, MakeNewNode, Message, Node, Runtime};
+
+/// Starts a node: does the `init` handshake, then builds a handler from the ready [`Node`]
+///
+/// Maelstrom may deliver messages before `init` once other nodes are up, so those are held
+/// back and handled, in order, once the handler exists.
+pub struct NodeBuilder {
+    node: Node,
+    /// Lines that arrived before `init`
+    early: Vec<String>,
+}
+
+impl NodeBuilder {
+    /// Wait for `init` on stdin, and reply to it
+    pub fn init() -> Result<Self> {
+        Self::init_from(std::io::stdin().lines())
+    }
+
+    /// Like [`NodeBuilder::init`], reading `lines` until `init` turns up
+    pub fn init_from(lines: impl IntoIterator<Item = io::Result<String>>) -> Result<Self> {
+        let mut early = vec![];
+
+        for line in lines {
+            let line = line?;
+            let is_init = serde_json::from_str::<Message<Body<Value>>>(&line)
+                .is_ok_and(|m| m.body.kind() == "init");
+
+            if is_init {
+                let node = Node::init(line)?;
+
+                if !early.is_empty() {
+                    eprintln!("Handling {} messages that arrived before init", early.len());
+                }
+                return Ok(Self { node, early });
+            }
+
+            eprintln!("Holding on to a message until init: {line}");
+            early.push(line);
+        }
+
+        bail!("Input closed before we got an init message")
+    }
+
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Build a handler from the node, then hand it every message, starting with any that
+    /// arrived before `init`
+    pub fn handle_requests<H: Handler>(self, build: impl FnOnce(Node) -> H) -> Result<()> {
+        let handler = build(self.node);
+        let stdin = read_stdin(self.early, handler.rpc());
+
+        handler.handle_stdin(stdin)
+    }
+
+    /// Like [`NodeBuilder::handle_requests`], with the handler driven by `runtime`
+    pub fn run<H: EventHandler>(
+        self,
+        runtime: Runtime<H::Event>,
+        build: impl FnOnce(Node) -> H,
+    ) -> Result<()> {
+        let handler = build(self.node);
+        let stdin = read_stdin(self.early, handler.rpc());
+
+        runtime.run_on(handler, stdin)
+    }
+}
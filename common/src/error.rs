@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+
+use crate::MsgId;
+
+/// The standard Maelstrom error codes
+///
+/// See https://github.com/jepsen-io/maelstrom/blob/main/doc/protocol.md#errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    Timeout,
+    NodeNotFound,
+    NotSupported,
+    TemporarilyUnavailable,
+    MalformedRequest,
+    Crash,
+    Abort,
+    KeyDoesNotExist,
+    KeyAlreadyExists,
+    PreconditionFailed,
+    TxnConflict,
+    Other(i64),
+}
+
+impl ErrorCode {
+    pub fn code(&self) -> i64 {
+        match self {
+            ErrorCode::Timeout => 0,
+            ErrorCode::NodeNotFound => 1,
+            ErrorCode::NotSupported => 10,
+            ErrorCode::TemporarilyUnavailable => 11,
+            ErrorCode::MalformedRequest => 12,
+            ErrorCode::Crash => 13,
+            ErrorCode::Abort => 14,
+            ErrorCode::KeyDoesNotExist => 20,
+            ErrorCode::KeyAlreadyExists => 21,
+            ErrorCode::PreconditionFailed => 22,
+            ErrorCode::TxnConflict => 30,
+            ErrorCode::Other(code) => *code,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            0 => ErrorCode::Timeout,
+            1 => ErrorCode::NodeNotFound,
+            10 => ErrorCode::NotSupported,
+            11 => ErrorCode::TemporarilyUnavailable,
+            12 => ErrorCode::MalformedRequest,
+            13 => ErrorCode::Crash,
+            14 => ErrorCode::Abort,
+            20 => ErrorCode::KeyDoesNotExist,
+            21 => ErrorCode::KeyAlreadyExists,
+            22 => ErrorCode::PreconditionFailed,
+            30 => ErrorCode::TxnConflict,
+            other => ErrorCode::Other(other),
+        }
+    }
+}
+
+/// An error a handler can return to have a specific Maelstrom error sent back to the client
+///
+/// Any other error returned from a handler is reported as [`ErrorCode::Crash`]
+#[derive(Debug, Clone)]
+pub struct MaelstromError {
+    pub code: ErrorCode,
+    pub text: String,
+}
+
+impl MaelstromError {
+    pub fn new(code: ErrorCode, text: impl Into<String>) -> Self {
+        Self {
+            code,
+            text: text.into(),
+        }
+    }
+
+    pub fn not_supported(text: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotSupported, text)
+    }
+
+    pub fn temporarily_unavailable(text: impl Into<String>) -> Self {
+        Self::new(ErrorCode::TemporarilyUnavailable, text)
+    }
+
+    pub fn malformed_request(text: impl Into<String>) -> Self {
+        Self::new(ErrorCode::MalformedRequest, text)
+    }
+
+    pub fn crash(text: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Crash, text)
+    }
+
+    pub fn into_msg(self, in_reply_to: MsgId) -> ErrorMsg {
+        ErrorMsg {
+            code: self.code.code(),
+            in_reply_to,
+            text: self.text,
+        }
+    }
+}
+
+impl std::fmt::Display for MaelstromError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "error {}: {}", self.code.code(), self.text)
+    }
+}
+
+impl std::error::Error for MaelstromError {}
+
+impl From<&color_eyre::Report> for MaelstromError {
+    fn from(report: &color_eyre::Report) -> Self {
+        match report.downcast_ref::<MaelstromError>() {
+            Some(e) => e.clone(),
+            None => MaelstromError::crash(format!("{report}")),
+        }
+    }
+}
+
+impl From<&serde_json::Error> for MaelstromError {
+    fn from(e: &serde_json::Error) -> Self {
+        // serde doesn't give us a structured way to tell an unknown `type` tag apart from a
+        // badly shaped body, so we have to look at the message
+        if e.to_string().starts_with("unknown variant") {
+            MaelstromError::not_supported(e.to_string())
+        } else {
+            MaelstromError::malformed_request(e.to_string())
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ErrorMsg {
+    pub code: i64,
+    pub in_reply_to: MsgId,
+    pub text: String,
+}
+
+impl ErrorMsg {
+    pub fn error_code(&self) -> ErrorCode {
+        ErrorCode::from_code(self.code)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum ErrorBody {
+    #[serde(rename = "error")]
+    Error(ErrorMsg),
+}
@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use color_eyre::eyre::{Report, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    CallOptions, ErrorCode, ErrorMsg, ExtraFields, Handler, MaelstromError, Message, MsgId,
+    NodeIdable, Outbox, Rpc,
+};
+
+pub const SEQ_KV: &str = "seq-kv";
+pub const LIN_KV: &str = "lin-kv";
+pub const LWW_KV: &str = "lww-kv";
+
+#[derive(Debug)]
+pub enum KvError {
+    KeyDoesNotExist,
+    PreconditionFailed(String),
+    Other(Report),
+}
+
+impl std::fmt::Display for KvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KvError::KeyDoesNotExist => write!(f, "key does not exist"),
+            KvError::PreconditionFailed(text) => write!(f, "precondition failed: {text}"),
+            KvError::Other(report) => write!(f, "{report}"),
+        }
+    }
+}
+
+impl std::error::Error for KvError {}
+
+impl From<Report> for KvError {
+    fn from(report: Report) -> Self {
+        match report.downcast_ref::<MaelstromError>() {
+            Some(MaelstromError {
+                code: ErrorCode::KeyDoesNotExist,
+                ..
+            }) => KvError::KeyDoesNotExist,
+            Some(MaelstromError {
+                code: ErrorCode::PreconditionFailed,
+                text,
+            }) => KvError::PreconditionFailed(text.clone()),
+            _ => KvError::Other(report),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadOk {
+    value: Value,
+}
+
+/// Typed client for one of Maelstrom's key-value services
+#[derive(Clone)]
+pub struct Kv {
+    rpc: Rpc,
+    service: String,
+    options: CallOptions,
+}
+
+impl Kv {
+    pub fn new(rpc: Rpc, service: &str) -> Self {
+        Self {
+            rpc,
+            service: service.to_owned(),
+            options: CallOptions::default(),
+        }
+    }
+
+    pub fn seq(rpc: Rpc) -> Self {
+        Self::new(rpc, SEQ_KV)
+    }
+
+    pub fn lin(rpc: Rpc) -> Self {
+        Self::new(rpc, LIN_KV)
+    }
+
+    pub fn lww(rpc: Rpc) -> Self {
+        Self::new(rpc, LWW_KV)
+    }
+
+    pub fn with_options(self, options: CallOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    fn call(&self, body: Value) -> Result<Value, KvError> {
+        Ok(self.rpc.call_blocking(&self.service, body, self.options)?)
+    }
+
+    pub fn read<K: Serialize, V: DeserializeOwned>(&self, key: K) -> Result<V, KvError> {
+        let reply = self.call(json!({ "type": "read", "key": key }))?;
+        let ReadOk { value } = serde_json::from_value(reply).map_err(Report::from)?;
+
+        Ok(serde_json::from_value(value).map_err(Report::from)?)
+    }
+
+    /// Like [`Kv::read`], but a missing key reads as `default`
+    pub fn read_or<K: Serialize, V: DeserializeOwned>(
+        &self,
+        key: K,
+        default: V,
+    ) -> Result<V, KvError> {
+        match self.read(key) {
+            Err(KvError::KeyDoesNotExist) => Ok(default),
+            other => other,
+        }
+    }
+
+    pub fn write<K: Serialize, V: Serialize>(&self, key: K, value: V) -> Result<(), KvError> {
+        self.call(json!({ "type": "write", "key": key, "value": value }))?;
+
+        Ok(())
+    }
+
+    /// Set `key` to `to` if it currently holds `from`
+    ///
+    /// With `create_if_not_exists` a missing key is created holding `to` instead of failing.
+    pub fn cas<K: Serialize, V: Serialize>(
+        &self,
+        key: K,
+        from: V,
+        to: V,
+        create_if_not_exists: bool,
+    ) -> Result<(), KvError> {
+        self.call(json!({
+            "type": "cas",
+            "key": key,
+            "from": from,
+            "to": to,
+            "create_if_not_exists": create_if_not_exists,
+        }))?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum KvRequestBody {
+    #[serde(rename = "read")]
+    Read { msg_id: MsgId, key: Value },
+    #[serde(rename = "write")]
+    Write {
+        msg_id: MsgId,
+        key: Value,
+        value: Value,
+    },
+    #[serde(rename = "cas")]
+    Cas {
+        msg_id: MsgId,
+        key: Value,
+        from: Value,
+        to: Value,
+        #[serde(default)]
+        create_if_not_exists: bool,
+    },
+}
+
+impl ExtraFields for KvRequestBody {}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum KvResponseBody {
+    #[serde(rename = "read_ok")]
+    Read { in_reply_to: MsgId, value: Value },
+    #[serde(rename = "write_ok")]
+    Write { in_reply_to: MsgId },
+    #[serde(rename = "cas_ok")]
+    Cas { in_reply_to: MsgId },
+    #[serde(rename = "error")]
+    Error(ErrorMsg),
+}
+
+/// An in-memory stand-in for Maelstrom's key-value services, for running nodes locally
+///
+/// Every operation is applied in the order it arrives, so it is as strong as `lin-kv` and
+/// serves equally well for `seq-kv` and `lww-kv`.
+pub struct MemoryKv {
+    service: String,
+    outbox: Outbox,
+    values: HashMap<String, Value>,
+    /// Off by default, as this usually runs inside a process that isn't ours to log for
+    log_messages: bool,
+}
+
+impl MemoryKv {
+    pub fn new(service: &str, outbox: Outbox) -> Self {
+        Self {
+            service: service.to_owned(),
+            outbox,
+            values: HashMap::new(),
+            log_messages: false,
+        }
+    }
+
+    pub fn with_logging(self, log_messages: bool) -> Self {
+        Self {
+            log_messages,
+            ..self
+        }
+    }
+
+    fn error(code: ErrorCode, in_reply_to: MsgId, text: String) -> KvResponseBody {
+        KvResponseBody::Error(MaelstromError::new(code, text).into_msg(in_reply_to))
+    }
+}
+
+impl NodeIdable for MemoryKv {
+    fn node_id(&self) -> &str {
+        &self.service
+    }
+}
+
+impl Handler for MemoryKv {
+    type RequestBody = KvRequestBody;
+    type ResponseBody = KvResponseBody;
+
+    fn send_message<Body: Serialize + Clone>(
+        &mut self,
+        m: Message<Body>,
+    ) -> color_eyre::Result<()> {
+        self.outbox.send(&m)
+    }
+
+    fn log_messages(&self) -> bool {
+        self.log_messages
+    }
+
+    fn try_handle_request(&mut self, body: &KvRequestBody) -> Result<Option<KvResponseBody>> {
+        Ok(Some(match body {
+            KvRequestBody::Read { msg_id, key } => match self.values.get(&key.to_string()) {
+                Some(value) => KvResponseBody::Read {
+                    in_reply_to: *msg_id,
+                    value: value.clone(),
+                },
+                None => Self::error(
+                    ErrorCode::KeyDoesNotExist,
+                    *msg_id,
+                    format!("key {key} does not exist"),
+                ),
+            },
+            KvRequestBody::Write { msg_id, key, value } => {
+                self.values.insert(key.to_string(), value.clone());
+
+                KvResponseBody::Write {
+                    in_reply_to: *msg_id,
+                }
+            }
+            KvRequestBody::Cas {
+                msg_id,
+                key,
+                from,
+                to,
+                create_if_not_exists,
+            } => match self.values.get(&key.to_string()) {
+                Some(current) if current == from => {
+                    self.values.insert(key.to_string(), to.clone());
+
+                    KvResponseBody::Cas {
+                        in_reply_to: *msg_id,
+                    }
+                }
+                Some(current) => Self::error(
+                    ErrorCode::PreconditionFailed,
+                    *msg_id,
+                    format!("expected {from} but {key} holds {current}"),
+                ),
+                None if *create_if_not_exists => {
+                    self.values.insert(key.to_string(), to.clone());
+
+                    KvResponseBody::Cas {
+                        in_reply_to: *msg_id,
+                    }
+                }
+                None => Self::error(
+                    ErrorCode::KeyDoesNotExist,
+                    *msg_id,
+                    format!("key {key} does not exist"),
+                ),
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossbeam::channel::{unbounded, Receiver};
+
+    use super::*;
+
+    fn kv() -> (MemoryKv, Receiver<String>) {
+        let (outbox, replies) = unbounded();
+
+        (MemoryKv::new(LIN_KV, Outbox::Channel(outbox)), replies)
+    }
+
+    /// Send `body` to the kv the way a node would, and return the body of its reply
+    fn call(kv: &mut MemoryKv, replies: &Receiver<String>, body: Value) -> Value {
+        let line = json!({ "src": "n1", "dest": LIN_KV, "body": body }).to_string();
+        kv.handle_line(&line).unwrap();
+
+        let reply: Value = serde_json::from_str(&replies.try_recv().unwrap()).unwrap();
+        assert_eq!(reply["dest"], "n1");
+
+        reply["body"].clone()
+    }
+
+    fn error_code(body: &Value) -> ErrorCode {
+        assert_eq!(body["type"], "error", "{body}");
+
+        ErrorCode::from_code(body["code"].as_i64().unwrap())
+    }
+
+    #[test]
+    fn read_missing_key() {
+        let (mut kv, replies) = kv();
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 1, "key": "a"}),
+        );
+
+        assert_eq!(error_code(&body), ErrorCode::KeyDoesNotExist);
+        assert_eq!(body["in_reply_to"], 1);
+    }
+
+    #[test]
+    fn write_then_read() {
+        let (mut kv, replies) = kv();
+
+        let written = json!({"type": "write", "msg_id": 1, "key": "a", "value": [1, 2]});
+        let body = call(&mut kv, &replies, written);
+        assert_eq!(body, json!({"type": "write_ok", "in_reply_to": 1}));
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 2, "key": "a"}),
+        );
+        assert_eq!(
+            body,
+            json!({"type": "read_ok", "in_reply_to": 2, "value": [1, 2]})
+        );
+    }
+
+    #[test]
+    fn keys_are_compared_as_json() {
+        let (mut kv, replies) = kv();
+
+        let written = json!({"type": "write", "msg_id": 1, "key": 1, "value": "number"});
+        call(&mut kv, &replies, written);
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 2, "key": "1"}),
+        );
+        assert_eq!(error_code(&body), ErrorCode::KeyDoesNotExist);
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 3, "key": 1}),
+        );
+        assert_eq!(body["value"], "number");
+    }
+
+    #[test]
+    fn cas_swaps_when_the_value_matches() {
+        let (mut kv, replies) = kv();
+        call(
+            &mut kv,
+            &replies,
+            json!({"type": "write", "msg_id": 1, "key": "a", "value": 1}),
+        );
+
+        let cas = json!({"type": "cas", "msg_id": 2, "key": "a", "from": 1, "to": 2});
+        let body = call(&mut kv, &replies, cas);
+        assert_eq!(body, json!({"type": "cas_ok", "in_reply_to": 2}));
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 3, "key": "a"}),
+        );
+        assert_eq!(body["value"], 2);
+    }
+
+    #[test]
+    fn cas_fails_when_the_value_differs() {
+        let (mut kv, replies) = kv();
+        call(
+            &mut kv,
+            &replies,
+            json!({"type": "write", "msg_id": 1, "key": "a", "value": 1}),
+        );
+
+        let cas = json!({"type": "cas", "msg_id": 2, "key": "a", "from": 5, "to": 2});
+        let body = call(&mut kv, &replies, cas);
+        assert_eq!(error_code(&body), ErrorCode::PreconditionFailed);
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 3, "key": "a"}),
+        );
+        assert_eq!(body["value"], 1);
+    }
+
+    #[test]
+    fn cas_on_a_missing_key() {
+        let (mut kv, replies) = kv();
+
+        let cas = json!({"type": "cas", "msg_id": 1, "key": "a", "from": 0, "to": 1});
+        let body = call(&mut kv, &replies, cas);
+        assert_eq!(error_code(&body), ErrorCode::KeyDoesNotExist);
+
+        let cas = json!({
+            "type": "cas",
+            "msg_id": 2,
+            "key": "a",
+            "from": 0,
+            "to": 1,
+            "create_if_not_exists": true,
+        });
+        let body = call(&mut kv, &replies, cas);
+        assert_eq!(body, json!({"type": "cas_ok", "in_reply_to": 2}));
+
+        let body = call(
+            &mut kv,
+            &replies,
+            json!({"type": "read", "msg_id": 3, "key": "a"}),
+        );
+        assert_eq!(body["value"], 1);
+    }
+}
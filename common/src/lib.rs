@@ -1,7 +1,40 @@
 use std::sync::{atomic::AtomicU64, Arc};
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{bail, Result};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub use macros::{reply, request};
+
+mod body;
+pub use body::*;
+
+mod error;
+pub use error::*;
+
+mod rpc;
+pub use rpc::*;
+
+mod kv;
+pub use kv::*;
+
+mod runtime;
+pub use runtime::*;
+
+mod timers;
+pub use timers::*;
+
+mod router;
+pub use router::*;
+
+mod builder;
+pub use builder::*;
+
+mod topology;
+pub use topology::*;
+
+mod settings;
+pub use settings::*;
 
 #[derive(Debug)]
 pub struct Node {
@@ -36,6 +69,24 @@ pub struct Message<Body: Clone> {
     pub body: Body,
     pub dest: String,
     pub src: String,
+    /// Any fields we don't know about, kept so they survive being passed on
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl<Body: Clone> Message<Body> {
+    pub fn new(src: impl Into<String>, dest: impl Into<String>, body: Body) -> Self {
+        Self {
+            body,
+            dest: dest.into(),
+            src: src.into(),
+            extra: Map::new(),
+        }
+    }
+
+    pub fn with_extra(self, extra: Map<String, Value>) -> Self {
+        Self { extra, ..self }
+    }
 }
 
 pub trait NodeIdable {
@@ -59,63 +110,140 @@ impl MsgIdAble for Node {
 }
 
 pub trait Handler: NodeIdable + Sized {
-    type RequestBody: Clone + for<'a> Deserialize<'a>;
+    type RequestBody: Clone + ExtraFields + for<'a> Deserialize<'a>;
     type ResponseBody: Serialize + Clone;
 
     fn respond_to(&mut self, m: Message<Self::RequestBody>) -> Result<()> {
-        let body = self.handle_request(&m.body);
+        let body = self.try_handle_message(&m)?;
 
         let Some(body) = body else {
             return Ok(());
         };
 
-        self.send_body(body, &m.src)
-    }
+        if !self.echo_extra() {
+            return self.send_body(body, &m.src);
+        }
 
-    fn send_message<Body: Serialize + Clone>(&mut self, m: Message<Body>) -> Result<()> {
-        let output = serde_json::to_string(&m)?;
+        // The reply's own fields win over any of the same name
+        let mut body = serde_json::to_value(body)?;
+        if let Value::Object(fields) = &mut body {
+            for (key, value) in m.body.extra_fields() {
+                fields.entry(key).or_insert(value);
+            }
+        }
 
-        eprintln!("Sending: {output}");
-        println!("{output}");
+        let reply = Message::new(self.node_id(), &m.src, body).with_extra(m.extra);
+        self.send_message(reply)
+    }
 
-        Ok(())
+    /// Whether replies carry the unknown fields of the message they reply to, and of its body,
+    /// so a newer peer sees its protocol extensions come back from us
+    fn echo_extra(&self) -> bool {
+        false
+    }
+
+    fn send_message<Body: Serialize + Clone>(&mut self, m: Message<Body>) -> Result<()> {
+        Outbox::Stdout.send(&m)
     }
 
     fn send_body<Body: Serialize + Clone>(&mut self, body: Body, dest: &str) -> Result<()> {
-        let m = Message {
-            body,
-            dest: dest.to_owned(),
-            src: self.node_id().to_owned(),
-        };
+        let m = Message::new(self.node_id(), dest, body);
 
         self.send_message(m)
     }
 
-    fn handle_request(&mut self, m: &Self::RequestBody) -> Option<Self::ResponseBody>;
+    /// The reply to a request, if it gets one
+    ///
+    /// Returning a [`MaelstromError`] sends that error back to the client, any other error is
+    /// reported as a crash. Either way the node keeps running.
+    fn try_handle_request(&mut self, m: &Self::RequestBody) -> Result<Option<Self::ResponseBody>>;
+
+    /// Like [`Handler::try_handle_request`], for handlers that need more than the body, such
+    /// as who sent it
+    fn try_handle_message(
+        &mut self,
+        m: &Message<Self::RequestBody>,
+    ) -> Result<Option<Self::ResponseBody>> {
+        self.try_handle_request(&m.body)
+    }
 
-    fn handle_requests(mut self) -> Result<()> {
-        let stdin = std::io::stdin();
+    /// Reply to the request in `raw` with a Maelstrom `error` body
+    ///
+    /// Replies (anything with an `in_reply_to`) and messages without a `msg_id` can't be
+    /// answered, so those errors are only logged.
+    fn send_error(&mut self, raw: &Message<Body<Value>>, error: MaelstromError) -> Result<()> {
+        eprintln!("Error handling message: {error}");
 
-        loop {
-            let mut buffer = String::new();
-            let bytes = stdin.read_line(&mut buffer)?;
+        if raw.body.is_reply() {
+            return Ok(());
+        }
+        let Some(msg_id) = raw.body.msg_id() else {
+            return Ok(());
+        };
 
-            if bytes != 0 && !buffer.is_empty() {
-                eprintln!("Received: {}", buffer);
+        self.send_body(ErrorBody::Error(error.into_msg(msg_id)), &raw.src)
+    }
 
-                let m = serde_json::from_str::<Message<Self::RequestBody>>(&buffer)?;
+    fn handle_line(&mut self, line: &str) -> Result<()> {
+        if self.log_messages() {
+            eprintln!("Received: {}", line);
+        }
 
-                self.respond_to(m)?;
+        let raw = match serde_json::from_str::<Message<Body<Value>>>(line) {
+            Ok(raw) => raw,
+            Err(e) => {
+                // Without a valid envelope we don't know who to send an error to
+                eprintln!("Dropping unparseable message: {e}");
+                return Ok(());
             }
+        };
+
+        let m = match serde_json::from_str::<Message<Self::RequestBody>>(line) {
+            Ok(m) => m,
+            Err(e) => return self.send_error(&raw, (&e).into()),
+        };
+
+        if let Err(e) = self.respond_to(m) {
+            self.send_error(&raw, (&e).into())?;
         }
+
+        Ok(())
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ErrorMsg {
-    pub code: i64,
-    pub in_reply_to: MsgId,
-    pub text: String,
+    /// Whether [`Handler::handle_line`] logs every message to stderr, as Maelstrom shows us
+    ///
+    /// Handlers running inside another process, whose stderr isn't their own, can turn it off.
+    fn log_messages(&self) -> bool {
+        true
+    }
+
+    /// The [`Rpc`] this handler makes calls with, so replies can be routed back to it
+    fn rpc(&self) -> Option<Rpc> {
+        None
+    }
+
+    fn handle_requests(self) -> Result<()> {
+        let stdin = read_stdin(vec![], self.rpc());
+
+        self.handle_stdin(stdin)
+    }
+
+    /// Like [`Handler::handle_requests`], with stdin already being read
+    fn handle_stdin(self, stdin: Stdin) -> Result<()> {
+        self.handle_lines(&stdin.lines)?;
+        stdin.join()
+    }
+
+    /// Handle messages from anywhere other than stdin, one JSON message per line
+    fn handle_lines(mut self, lines: impl IntoIterator<Item = String>) -> Result<()> {
+        for line in lines {
+            if !line.is_empty() {
+                self.handle_line(&line)?;
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -151,6 +279,8 @@ pub enum InitBody {
     Init(Init),
 }
 
+impl ExtraFields for InitBody {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum InitBodyResponse {
@@ -168,6 +298,9 @@ impl MakeNewNode for Node {
             }),
             ..
         } = &m;
+        if !node_ids.contains(node_id) {
+            bail!("init gave us node id {node_id}, which isn't one of {node_ids:?}");
+        }
 
         let ids = IdGenerator {
             next_id: AtomicU64::new(0),
@@ -191,11 +324,11 @@ impl Handler for Node {
 
     type ResponseBody = InitBodyResponse;
 
-    fn handle_request(&mut self, m: &Self::RequestBody) -> Option<Self::ResponseBody> {
+    fn try_handle_request(&mut self, m: &Self::RequestBody) -> Result<Option<Self::ResponseBody>> {
         match m {
-            InitBody::Init(init) => Some(InitBodyResponse::InitResp(
+            InitBody::Init(init) => Ok(Some(InitBodyResponse::InitResp(
                 init.response(self.generate_msg_id()),
-            )),
+            ))),
         }
     }
 }
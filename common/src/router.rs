@@ -0,0 +1,362 @@
+use std::{collections::HashMap, time::Instant};
+
+use color_eyre::eyre::Result;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    run_due_timers, Body, EventHandler, ExtraFields, Handler, Kinds, MaelstromError, Message, Node,
+    NodeIdable, Paired, Payload, Timers,
+};
+
+/// One workload's part of a node, handling only the message types of its `Request`
+///
+/// Register services on a [`Router`] to run several workloads on one node. A router driven by
+/// a [`crate::Runtime`] also runs each service's timers, and shuts them down once stdin closes.
+pub trait Service: Sized {
+    type Request: Kinds + Paired + Payload + Clone + Serialize + DeserializeOwned;
+
+    fn handle(
+        &mut self,
+        node: &Node,
+        request: &Body<Self::Request>,
+    ) -> Result<Option<Body<<Self::Request as Paired>::Reply>>>;
+
+    /// Like [`Service::handle`], for services that need more than the body, such as who sent
+    /// it
+    fn handle_message(
+        &mut self,
+        node: &Node,
+        m: &Message<Body<Self::Request>>,
+    ) -> Result<Option<Body<<Self::Request as Paired>::Reply>>> {
+        self.handle(node, &m.body)
+    }
+
+    /// The service's timers, see [`Timers`]
+    fn timers(&mut self) -> Option<&mut Timers<Self>> {
+        None
+    }
+
+    /// Called once stdin closes, before the runtime returns
+    fn shutdown(&mut self, _node: &Node) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`Service`] with its types erased, so services with different types can share a router
+///
+/// With `echo_extra`, replies carry the fields of the request's body its service didn't know.
+trait Route {
+    fn handle(
+        &mut self,
+        node: &Node,
+        body: &Body<Value>,
+        echo_extra: bool,
+    ) -> Result<Option<Body<Value>>>;
+
+    fn handle_message(
+        &mut self,
+        node: &Node,
+        m: &Message<Body<Value>>,
+        echo_extra: bool,
+    ) -> Result<Option<Body<Value>>>;
+
+    fn next_timer(&mut self) -> Option<Instant>;
+
+    fn run_timers(&mut self, now: Instant) -> Result<()>;
+
+    fn shutdown(&mut self, node: &Node) -> Result<()>;
+}
+
+struct Routed<S>(S);
+
+impl<S> Routed<S>
+where
+    S: Service,
+    <S::Request as Paired>::Reply: Serialize,
+{
+    fn request(body: &Body<Value>) -> Result<Body<S::Request>> {
+        let request = serde_json::to_value(body)
+            .and_then(serde_json::from_value)
+            .map_err(|e| MaelstromError::from(&e))?;
+
+        Ok(request)
+    }
+
+    fn reply(
+        request: &Body<S::Request>,
+        reply: Option<Body<<S::Request as Paired>::Reply>>,
+        echo_extra: bool,
+    ) -> Result<Option<Body<Value>>> {
+        let Some(reply) = reply else {
+            return Ok(None);
+        };
+
+        let mut reply = serde_json::to_value(reply)?;
+        if let (true, Value::Object(fields)) = (echo_extra, &mut reply) {
+            // The reply's own fields win over any of the same name
+            for (key, value) in request.extra_fields() {
+                fields.entry(key).or_insert(value);
+            }
+        }
+
+        Ok(Some(serde_json::from_value(reply)?))
+    }
+}
+
+impl<S> Route for Routed<S>
+where
+    S: Service,
+    <S::Request as Paired>::Reply: Serialize,
+{
+    fn handle(
+        &mut self,
+        node: &Node,
+        body: &Body<Value>,
+        echo_extra: bool,
+    ) -> Result<Option<Body<Value>>> {
+        let request = Self::request(body)?;
+        let reply = self.0.handle(node, &request)?;
+
+        Self::reply(&request, reply, echo_extra)
+    }
+
+    fn handle_message(
+        &mut self,
+        node: &Node,
+        m: &Message<Body<Value>>,
+        echo_extra: bool,
+    ) -> Result<Option<Body<Value>>> {
+        let request = Message {
+            body: Self::request(&m.body)?,
+            dest: m.dest.clone(),
+            src: m.src.clone(),
+            extra: m.extra.clone(),
+        };
+
+        let reply = self.0.handle_message(node, &request)?;
+
+        Self::reply(&request.body, reply, echo_extra)
+    }
+
+    fn next_timer(&mut self) -> Option<Instant> {
+        self.0.timers().and_then(|timers| timers.next_due())
+    }
+
+    fn run_timers(&mut self, now: Instant) -> Result<()> {
+        run_due_timers(&mut self.0, now, S::timers)
+    }
+
+    fn shutdown(&mut self, node: &Node) -> Result<()> {
+        self.0.shutdown(node)
+    }
+}
+
+/// A [`Handler`] that passes each message to whichever [`Service`] handles its `type`
+///
+/// `init` is handled once, by [`NodeBuilder`], before the router gets the node. Anything no
+/// service handles is answered with a `not-supported` error.
+///
+/// [`NodeBuilder`]: crate::NodeBuilder
+pub struct Router {
+    node: Node,
+    services: Vec<Box<dyn Route>>,
+    /// Each message type, and the index in `services` of the one that handles it
+    routes: HashMap<&'static str, usize>,
+    echo_extra: bool,
+}
+
+impl Router {
+    pub fn new(node: Node) -> Self {
+        Self {
+            node,
+            services: vec![],
+            routes: HashMap::new(),
+            echo_extra: false,
+        }
+    }
+
+    /// Have replies carry the unknown fields of the message they reply to, and of its body, as
+    /// read by the service that handles it. See [`Handler::echo_extra`]
+    pub fn with_echo_extra(self, echo_extra: bool) -> Self {
+        Self { echo_extra, ..self }
+    }
+
+    /// Route every message type `service` handles to it
+    ///
+    /// Panics if another service already handles one of them, as there's no telling which of
+    /// them should get it.
+    pub fn with_service<S>(mut self, service: S) -> Self
+    where
+        S: Service + 'static,
+        <S::Request as Paired>::Reply: Serialize,
+    {
+        let index = self.services.len();
+        for kind in S::Request::KINDS {
+            if self.routes.insert(kind, index).is_some() {
+                panic!("More than one service handles {kind} messages");
+            }
+        }
+        self.services.push(Box::new(Routed(service)));
+
+        self
+    }
+
+    /// The index in `services` of the one that handles `body`
+    fn route(&self, body: &Body<Value>) -> Result<usize> {
+        let Some(index) = self.routes.get(body.kind()) else {
+            return Err(MaelstromError::not_supported(format!(
+                "No service handles {} messages",
+                body.kind()
+            ))
+            .into());
+        };
+
+        Ok(*index)
+    }
+}
+
+impl NodeIdable for Router {
+    fn node_id(&self) -> &str {
+        self.node.node_id()
+    }
+}
+
+impl Handler for Router {
+    type RequestBody = Body<Value>;
+    type ResponseBody = Body<Value>;
+
+    fn try_handle_request(&mut self, body: &Body<Value>) -> Result<Option<Body<Value>>> {
+        let index = self.route(body)?;
+
+        self.services[index].handle(&self.node, body, self.echo_extra)
+    }
+
+    fn try_handle_message(&mut self, m: &Message<Body<Value>>) -> Result<Option<Body<Value>>> {
+        let index = self.route(&m.body)?;
+
+        self.services[index].handle_message(&self.node, m, self.echo_extra)
+    }
+
+    fn echo_extra(&self) -> bool {
+        self.echo_extra
+    }
+}
+
+/// Events are messages handed to the node by its own threads, through
+/// [`crate::Runtime::events`], and are routed like any other
+impl EventHandler for Router {
+    type Event = Message<Body<Value>>;
+
+    fn handle_event(&mut self, event: Message<Body<Value>>) -> Result<()> {
+        self.respond_to(event)
+    }
+
+    fn next_timer(&mut self) -> Option<Instant> {
+        self.services
+            .iter_mut()
+            .filter_map(|service| service.next_timer())
+            .min()
+    }
+
+    fn run_timers(&mut self, now: Instant) -> Result<()> {
+        for service in &mut self.services {
+            service.run_timers(now)?;
+        }
+
+        Ok(())
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        for service in &mut self.services {
+            service.shutdown(&self.node)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicU64, Arc};
+
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use super::*;
+    use crate::{IdGenerator, Payload};
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    enum Request {
+        Echo { echo: String },
+    }
+
+    /// What `#[request(reply = Reply)]` would generate
+    impl Payload for Request {
+        fn kind(&self) -> &str {
+            "echo"
+        }
+
+        fn fields(kind: &str) -> Option<&'static [&'static str]> {
+            (kind == "echo").then_some(&["echo"])
+        }
+    }
+
+    impl Kinds for Request {
+        const KINDS: &'static [&'static str] = &["echo"];
+    }
+
+    impl Paired for Request {
+        type Reply = Reply;
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(tag = "type", rename_all = "snake_case")]
+    enum Reply {
+        EchoOk { echo: String },
+    }
+
+    struct Echo;
+
+    impl Service for Echo {
+        type Request = Request;
+
+        fn handle(&mut self, node: &Node, request: &Body<Request>) -> Result<Option<Body<Reply>>> {
+            let Request::Echo { echo } = &request.payload;
+
+            Ok(Some(
+                request.reply(node, Reply::EchoOk { echo: echo.clone() }),
+            ))
+        }
+    }
+
+    fn router() -> Router {
+        let ids = Arc::new(IdGenerator {
+            next_id: AtomicU64::new(0),
+        });
+
+        Router::new(Node::new("n0".to_owned(), vec!["n0".to_owned()], ids)).with_service(Echo)
+    }
+
+    fn reply(mut router: Router) -> Value {
+        let request =
+            serde_json::from_value(json!({"type": "echo", "msg_id": 1, "echo": "hi", "trace": 7}))
+                .unwrap();
+        let reply = router.try_handle_request(&request).unwrap().unwrap();
+
+        serde_json::to_value(reply).unwrap()
+    }
+
+    #[test]
+    fn replies_echo_fields_the_service_didnt_know_when_asked() {
+        assert_eq!(
+            reply(router().with_echo_extra(true)),
+            json!({"type": "echo_ok", "msg_id": 0, "in_reply_to": 1, "echo": "hi", "trace": 7})
+        );
+        assert_eq!(
+            reply(router()),
+            json!({"type": "echo_ok", "msg_id": 0, "in_reply_to": 1, "echo": "hi"})
+        );
+    }
+}
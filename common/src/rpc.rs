@@ -0,0 +1,285 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{bail, Result};
+use crossbeam::channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Body, ErrorCode, ErrorMsg, IdGenerator, MaelstromError, Message, MsgId, MsgIdAble, Node,
+};
+
+/// Where a node's outgoing messages go
+#[derive(Debug, Clone)]
+pub enum Outbox {
+    Stdout,
+    Channel(Sender<String>),
+}
+
+impl Outbox {
+    pub fn send<Body: Serialize + Clone>(&self, m: &Message<Body>) -> Result<()> {
+        let output = serde_json::to_string(m)?;
+
+        match self {
+            Outbox::Stdout => {
+                eprintln!("Sending: {output}");
+
+                // Unlike println!, this doesn't panic if whoever is reading us has gone away
+                let mut stdout = std::io::stdout().lock();
+                writeln!(stdout, "{output}")?;
+                stdout.flush()?;
+            }
+            Outbox::Channel(sender) => sender.send(output)?,
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CallOptions {
+    /// How long to wait for a reply before resending, or giving up once out of retries
+    pub timeout: Duration,
+    /// How many times to resend the request before failing with [`ErrorCode::Timeout`]
+    pub retries: u32,
+}
+
+impl Default for CallOptions {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(1),
+            retries: 0,
+        }
+    }
+}
+
+pub type RpcResult = std::result::Result<Value, MaelstromError>;
+
+type Callback = Box<dyn FnOnce(RpcResult) + Send>;
+
+struct PendingCall {
+    message: Message<Value>,
+    deadline: Instant,
+    retries_left: u32,
+    options: CallOptions,
+    callback: Callback,
+}
+
+struct RpcInner {
+    node_id: String,
+    ids: Arc<IdGenerator>,
+    outbox: Outbox,
+    pending: Mutex<HashMap<MsgId, PendingCall>>,
+    /// Pokes the timeout thread whenever a new deadline shows up
+    wakeup: Sender<()>,
+}
+
+/// Requests from this node to other nodes or Maelstrom services
+///
+/// Every call gets a fresh `msg_id`, and is resolved when a message with a matching
+/// `in_reply_to` is passed to [`Rpc::handle_reply`]. [`Handler::handle_requests`] does this
+/// for any handler that returns its `Rpc` from [`Handler::rpc`], before the handler sees the
+/// message. Replies are read on their own thread, so a handler is free to block on
+/// [`Rpc::call_blocking`].
+///
+/// [`Handler::handle_requests`]: crate::Handler::handle_requests
+/// [`Handler::rpc`]: crate::Handler::rpc
+#[derive(Clone)]
+pub struct Rpc {
+    inner: Arc<RpcInner>,
+}
+
+impl Rpc {
+    pub fn new(node: &Node) -> Self {
+        Self::with_outbox(node, Outbox::Stdout)
+    }
+
+    pub fn with_outbox(node: &Node, outbox: Outbox) -> Self {
+        let (wakeup, wakeup_receiver) = unbounded();
+
+        let inner = Arc::new(RpcInner {
+            node_id: node.id.clone(),
+            ids: Arc::clone(&node.ids),
+            outbox,
+            pending: Mutex::new(HashMap::new()),
+            wakeup,
+        });
+
+        let weak = Arc::downgrade(&inner);
+        std::thread::spawn(move || expire_calls(weak, wakeup_receiver));
+
+        Self { inner }
+    }
+
+    /// Send `body` to `dest`, calling `callback` with the reply body
+    ///
+    /// Any `msg_id` already in `body` is replaced with a fresh one.
+    ///
+    /// `error` replies are passed to the callback as a [`MaelstromError`], and so is running
+    /// out of retries. The callback runs on whichever thread saw the reply or the timeout, so
+    /// it should be quick.
+    pub fn call_with_callback<Body, F>(
+        &self,
+        dest: &str,
+        body: Body,
+        options: CallOptions,
+        callback: F,
+    ) -> Result<MsgId>
+    where
+        Body: Serialize,
+        F: FnOnce(RpcResult) + Send + 'static,
+    {
+        let msg_id = self.inner.ids.generate_msg_id();
+
+        let mut body = serde_json::to_value(body)?;
+        let Some(fields) = body.as_object_mut() else {
+            bail!("RPC bodies must serialize to JSON objects, got {body}");
+        };
+        fields.insert("msg_id".to_owned(), msg_id.into());
+
+        let message = Message::new(&self.inner.node_id, dest, body);
+        // Register the call before sending, a fast reply must not beat us to the map
+        let call = PendingCall {
+            message: message.clone(),
+            deadline: Instant::now() + options.timeout,
+            retries_left: options.retries,
+            options,
+            callback: Box::new(callback),
+        };
+        self.inner.pending.lock().unwrap().insert(msg_id, call);
+
+        if let Err(e) = self.inner.outbox.send(&message) {
+            self.inner.pending.lock().unwrap().remove(&msg_id);
+            return Err(e);
+        }
+        let _ = self.inner.wakeup.send(());
+
+        Ok(msg_id)
+    }
+
+    /// Like [`Rpc::call_with_callback`], but the result is delivered on a channel
+    pub fn call<Body: Serialize>(
+        &self,
+        dest: &str,
+        body: Body,
+        options: CallOptions,
+    ) -> Result<Receiver<RpcResult>> {
+        let (sender, receiver) = bounded(1);
+        self.call_with_callback(dest, body, options, move |result| {
+            let _ = sender.send(result);
+        })?;
+
+        Ok(receiver)
+    }
+
+    /// Send `body` to `dest` and wait for the reply, deserialized as `Reply`
+    ///
+    /// Failed calls return a [`MaelstromError`] which callers can get back with
+    /// `downcast_ref` to look at its [`ErrorCode`].
+    pub fn call_blocking<Body: Serialize, Reply: DeserializeOwned>(
+        &self,
+        dest: &str,
+        body: Body,
+        options: CallOptions,
+    ) -> Result<Reply> {
+        let reply = self.call(dest, body, options)?.recv()??;
+
+        Ok(serde_json::from_value(reply)?)
+    }
+
+    /// Resolve the call `message` is a reply to, if there is one
+    ///
+    /// Returns false for anything that isn't a reply to an outstanding call, which should then
+    /// be handled as usual.
+    pub fn handle_reply(&self, message: &Message<Body<Value>>) -> bool {
+        let Some(in_reply_to) = message.body.in_reply_to() else {
+            return false;
+        };
+
+        let Some(call) = self.inner.pending.lock().unwrap().remove(&in_reply_to) else {
+            return false;
+        };
+
+        let body = match serde_json::to_value(&message.body) {
+            Ok(body) => body,
+            Err(e) => {
+                (call.callback)(Err(MaelstromError::malformed_request(e.to_string())));
+                return true;
+            }
+        };
+        let result = if message.body.kind() == "error" {
+            match serde_json::from_value::<ErrorMsg>(body) {
+                Ok(e) => Err(MaelstromError::new(e.error_code(), e.text)),
+                Err(e) => Err(MaelstromError::malformed_request(e.to_string())),
+            }
+        } else {
+            Ok(body)
+        };
+        (call.callback)(result);
+
+        true
+    }
+}
+
+impl RpcInner {
+    /// Resend or fail every call past its deadline, and return the next deadline
+    fn expire_due(&self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut expired = vec![];
+
+        let next_deadline = {
+            let mut pending = self.pending.lock().unwrap();
+
+            let due: Vec<MsgId> = pending
+                .iter()
+                .filter(|(_, call)| call.deadline <= now)
+                .map(|(msg_id, _)| *msg_id)
+                .collect();
+
+            for msg_id in due {
+                let call = pending.get_mut(&msg_id).unwrap();
+
+                if call.retries_left > 0 {
+                    // Retries reuse the msg_id, so a late reply to any attempt resolves the call
+                    call.retries_left -= 1;
+                    call.deadline = now + call.options.timeout;
+                    if let Err(e) = self.outbox.send(&call.message) {
+                        eprintln!("Could not resend {msg_id}: {e}");
+                    }
+                } else {
+                    expired.push(pending.remove(&msg_id).unwrap());
+                }
+            }
+
+            pending.values().map(|call| call.deadline).min()
+        };
+
+        for call in expired {
+            let text = format!(
+                "{} did not reply to {} in time",
+                call.message.dest, call.message.body
+            );
+            (call.callback)(Err(MaelstromError::new(ErrorCode::Timeout, text)));
+        }
+
+        next_deadline
+    }
+}
+
+fn expire_calls(inner: Weak<RpcInner>, wakeup: Receiver<()>) {
+    while let Some(next_deadline) = inner.upgrade().map(|inner| inner.expire_due()) {
+        let woken = match next_deadline {
+            Some(deadline) => wakeup.recv_deadline(deadline),
+            None => wakeup.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        if let Err(RecvTimeoutError::Disconnected) = woken {
+            break;
+        }
+    }
+}
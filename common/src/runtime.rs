@@ -0,0 +1,172 @@
+use std::{io::Write, thread::JoinHandle, time::Instant};
+
+use color_eyre::eyre::{eyre, Result};
+use crossbeam::channel::{never, select, unbounded, Receiver, Sender};
+use serde_json::Value;
+
+use crate::{run_due_timers, Body, Handler, Message, Rpc, Timers};
+
+/// Lines of stdin, read on their own thread by [`read_stdin`]
+pub struct Stdin {
+    /// Closes when stdin does, or reading it fails
+    pub lines: Receiver<String>,
+    reader: JoinHandle<Result<()>>,
+}
+
+impl Stdin {
+    /// Wait for the reader to stop, returning the error that stopped it if it wasn't EOF
+    pub fn join(self) -> Result<()> {
+        self.reader
+            .join()
+            .map_err(|_| eyre!("The stdin reader panicked"))?
+    }
+}
+
+/// Read stdin on its own thread, one message per line
+///
+/// Replies to calls made with `rpc` are resolved right here, so they still arrive while the
+/// node is blocked waiting on one. Everything else comes out of [`Stdin::lines`], starting with
+/// the `early` lines, which were read before the node was ready for them.
+pub fn read_stdin(early: Vec<String>, rpc: Option<Rpc>) -> Stdin {
+    let (sender, receiver) = unbounded();
+    for line in early {
+        // We hold the receiver, so this can't fail
+        let _ = sender.send(line);
+    }
+
+    let reader = std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let line = line?;
+
+            if let Some(rpc) = &rpc {
+                let is_reply = serde_json::from_str::<Message<Body<Value>>>(&line)
+                    .map(|raw| rpc.handle_reply(&raw))
+                    .unwrap_or(false);
+
+                if is_reply {
+                    eprintln!("Received reply: {line}");
+                    continue;
+                }
+            }
+
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    });
+
+    Stdin {
+        lines: receiver,
+        reader,
+    }
+}
+
+/// A [`Handler`] that also does work that isn't a reply to a message
+pub trait EventHandler: Handler {
+    /// Sent to the node from other threads through [`Runtime::events`]
+    type Event: Send + 'static;
+
+    fn handle_event(&mut self, _event: Self::Event) -> Result<()> {
+        Ok(())
+    }
+
+    /// The timers the runtime should run for this handler, see [`Timers`]
+    fn timers(&mut self) -> Option<&mut Timers<Self>> {
+        None
+    }
+
+    /// When the next of the handler's timers is due
+    ///
+    /// Handlers whose timers aren't their own [`Timers`], like a [`crate::Router`]'s services',
+    /// override this and [`EventHandler::run_timers`] instead of [`EventHandler::timers`].
+    fn next_timer(&mut self) -> Option<Instant> {
+        self.timers().and_then(|timers| timers.next_due())
+    }
+
+    /// Run every timer that is due at `now`
+    fn run_timers(&mut self, now: Instant) -> Result<()> {
+        run_due_timers(self, now, Self::timers)
+    }
+
+    /// Called once stdin closes, before the runtime returns
+    fn shutdown(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Drives an [`EventHandler`] from a single thread
+///
+/// The thread sleeps until a message arrives on stdin, an event arrives from another thread,
+/// or the handler's next timer is due, whichever comes first. Once stdin closes it shuts the
+/// handler down and flushes stdout. The first error from the handler or the stdin reader
+/// stops it, and is returned.
+pub struct Runtime<Event> {
+    events: Sender<Event>,
+    event_receiver: Receiver<Event>,
+}
+
+impl<Event: Send + 'static> Default for Runtime<Event> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Event: Send + 'static> Runtime<Event> {
+    pub fn new() -> Self {
+        let (events, event_receiver) = unbounded();
+
+        Self {
+            events,
+            event_receiver,
+        }
+    }
+
+    /// Hand this to other threads so they can send events to the node
+    pub fn events(&self) -> Sender<Event> {
+        self.events.clone()
+    }
+
+    pub fn run<H: EventHandler<Event = Event>>(self, handler: H) -> Result<()> {
+        let stdin = read_stdin(vec![], handler.rpc());
+
+        self.run_on(handler, stdin)
+    }
+
+    /// Like [`Runtime::run`], with stdin already being read
+    pub fn run_on<H: EventHandler<Event = Event>>(
+        self,
+        mut handler: H,
+        stdin: Stdin,
+    ) -> Result<()> {
+        let lines = &stdin.lines;
+
+        loop {
+            let wakeup = match handler.next_timer() {
+                Some(at) => crossbeam::channel::at(at),
+                None => never(),
+            };
+
+            select! {
+                recv(lines) -> line => match line {
+                    Ok(line) if line.is_empty() => {}
+                    Ok(line) => handler.handle_line(&line)?,
+                    Err(_) => break,
+                },
+                recv(self.event_receiver) -> event => {
+                    // We hold a sender ourselves, so the channel never disconnects
+                    if let Ok(event) = event {
+                        handler.handle_event(event)?;
+                    }
+                },
+                recv(wakeup) -> _ => handler.run_timers(Instant::now())?,
+            }
+        }
+
+        handler.shutdown()?;
+        std::io::stdout().flush()?;
+
+        stdin.join()
+    }
+}
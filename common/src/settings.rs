@@ -0,0 +1,17 @@
+/// The value after the last `flag` on the command line, or failing that the environment
+/// variable `var`
+///
+/// Maelstrom starts nodes without arguments, so every flag a node reads has an environment
+/// variable to fall back on.
+pub fn setting(flag: &str, var: &str) -> Option<String> {
+    let mut value = std::env::var(var).ok();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            value = args.next();
+        }
+    }
+
+    value
+}
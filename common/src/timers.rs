@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use rand::Rng;
+
+pub type TimerId = u64;
+
+type Callback<H> = Box<dyn FnMut(&mut H) -> Result<()>>;
+
+struct Timer<H> {
+    due: Instant,
+    period: Option<Duration>,
+    jitter: Duration,
+    callback: Callback<H>,
+}
+
+/// One-shot and periodic callbacks, run on the node's event loop by [`crate::Runtime`]
+///
+/// A handler owns its `Timers` and hands them to the runtime through
+/// [`crate::EventHandler::timers`]. Callbacks get the handler itself, so they can do
+/// anything a message handler can, including scheduling or cancelling timers.
+pub struct Timers<H> {
+    next_id: TimerId,
+    timers: HashMap<TimerId, Timer<H>>,
+    /// Timers whose callbacks are running right now, and which of those got cancelled
+    running: HashSet<TimerId>,
+    cancelled: HashSet<TimerId>,
+}
+
+impl<H> Default for Timers<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A random duration up to `max`, to spread out things that would otherwise happen together
+pub fn jitter(max: Duration) -> Duration {
+    if max.is_zero() {
+        Duration::ZERO
+    } else {
+        rand::thread_rng().gen_range(Duration::ZERO..max)
+    }
+}
+
+fn jittered(base: Instant, delay: Duration, max_jitter: Duration) -> Instant {
+    base + delay + jitter(max_jitter)
+}
+
+impl<H> Timers<H> {
+    pub fn new() -> Self {
+        Self {
+            next_id: 0,
+            timers: HashMap::new(),
+            running: HashSet::new(),
+            cancelled: HashSet::new(),
+        }
+    }
+
+    fn insert(&mut self, timer: Timer<H>) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.insert(id, timer);
+
+        id
+    }
+
+    /// Run `callback` once, `delay` from now
+    pub fn after<F>(&mut self, delay: Duration, callback: F) -> TimerId
+    where
+        F: FnMut(&mut H) -> Result<()> + 'static,
+    {
+        self.insert(Timer {
+            due: Instant::now() + delay,
+            period: None,
+            jitter: Duration::ZERO,
+            callback: Box::new(callback),
+        })
+    }
+
+    /// Run `callback` every `period`, each run pushed back by a random amount up to `jitter`
+    /// so nodes started together don't all fire together
+    pub fn every<F>(&mut self, period: Duration, jitter: Duration, callback: F) -> TimerId
+    where
+        F: FnMut(&mut H) -> Result<()> + 'static,
+    {
+        self.insert(Timer {
+            due: jittered(Instant::now(), period, jitter),
+            period: Some(period),
+            jitter,
+            callback: Box::new(callback),
+        })
+    }
+
+    /// Returns false if the timer already fired, or was already cancelled
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        if self.running.contains(&id) {
+            return self.cancelled.insert(id);
+        }
+
+        self.timers.remove(&id).is_some()
+    }
+
+    pub fn next_due(&self) -> Option<Instant> {
+        self.timers.values().map(|timer| timer.due).min()
+    }
+}
+
+/// Run every timer of `handler` that is due at `now`, with `timers_of` finding its [`Timers`]
+pub(crate) fn run_due_timers<H>(
+    handler: &mut H,
+    now: Instant,
+    timers_of: fn(&mut H) -> Option<&mut Timers<H>>,
+) -> Result<()> {
+    let Some(timers) = timers_of(handler) else {
+        return Ok(());
+    };
+    let due: Vec<TimerId> = timers
+        .timers
+        .iter()
+        .filter(|(_, timer)| timer.due <= now)
+        .map(|(id, _)| *id)
+        .collect();
+
+    for id in due {
+        let Some(timers) = timers_of(handler) else {
+            break;
+        };
+        let Some(mut timer) = timers.timers.remove(&id) else {
+            // An earlier callback cancelled it
+            continue;
+        };
+        timers.running.insert(id);
+
+        let result = (timer.callback)(handler);
+
+        if let Some(timers) = timers_of(handler) {
+            timers.running.remove(&id);
+            let cancelled = timers.cancelled.remove(&id);
+
+            if let (Some(period), false) = (timer.period, cancelled) {
+                timer.due = jittered(now, period, timer.jitter);
+                timers.timers.insert(id, timer);
+            }
+        }
+
+        result?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{EventHandler, Handler, NodeIdable};
+
+    const MS: Duration = Duration::from_millis(1);
+
+    /// Records which of its timers fired
+    #[derive(Default)]
+    struct Ticker {
+        timers: Timers<Ticker>,
+        fired: Vec<&'static str>,
+        ids: HashMap<&'static str, TimerId>,
+    }
+
+    impl NodeIdable for Ticker {
+        fn node_id(&self) -> &str {
+            "n0"
+        }
+    }
+
+    impl Handler for Ticker {
+        type RequestBody = Value;
+        type ResponseBody = Value;
+
+        fn try_handle_request(&mut self, _m: &Value) -> Result<Option<Value>> {
+            Ok(None)
+        }
+    }
+
+    impl EventHandler for Ticker {
+        type Event = ();
+
+        fn timers(&mut self) -> Option<&mut Timers<Self>> {
+            Some(&mut self.timers)
+        }
+    }
+
+    fn fire(name: &'static str) -> impl FnMut(&mut Ticker) -> Result<()> {
+        move |ticker| {
+            ticker.fired.push(name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn one_shot_timers_fire_once_when_due() {
+        let mut ticker = Ticker::default();
+        let start = Instant::now();
+        ticker.timers.after(10 * MS, fire("a"));
+
+        run_due_timers(&mut ticker, start, Ticker::timers).unwrap();
+        assert!(ticker.fired.is_empty());
+
+        run_due_timers(&mut ticker, start + 20 * MS, Ticker::timers).unwrap();
+        run_due_timers(&mut ticker, start + 40 * MS, Ticker::timers).unwrap();
+        assert_eq!(ticker.fired, ["a"]);
+        assert_eq!(ticker.timers.next_due(), None);
+    }
+
+    #[test]
+    fn cancelled_timers_never_fire() {
+        let mut ticker = Ticker::default();
+        let id = ticker.timers.after(10 * MS, fire("a"));
+
+        assert!(ticker.timers.cancel(id));
+        assert!(!ticker.timers.cancel(id));
+
+        run_due_timers(&mut ticker, Instant::now() + 20 * MS, Ticker::timers).unwrap();
+        assert!(ticker.fired.is_empty());
+    }
+
+    #[test]
+    fn periodic_timers_reschedule_from_when_they_ran() {
+        let mut ticker = Ticker::default();
+        let id = ticker.timers.every(10 * MS, Duration::ZERO, fire("a"));
+
+        let now = Instant::now() + 15 * MS;
+        run_due_timers(&mut ticker, now, Ticker::timers).unwrap();
+        assert_eq!(ticker.timers.next_due(), Some(now + 10 * MS));
+
+        run_due_timers(&mut ticker, now + 10 * MS, Ticker::timers).unwrap();
+        assert_eq!(ticker.fired, ["a", "a"]);
+
+        assert!(ticker.timers.cancel(id));
+        assert_eq!(ticker.timers.next_due(), None);
+    }
+
+    #[test]
+    fn jitter_only_pushes_periodic_timers_back() {
+        let mut ticker = Ticker::default();
+        let before = Instant::now();
+        ticker.timers.every(10 * MS, 5 * MS, fire("a"));
+        let after = Instant::now();
+
+        let due = ticker.timers.next_due().unwrap();
+        assert!(due >= before + 10 * MS);
+        assert!(due < after + 15 * MS);
+    }
+
+    #[test]
+    fn periodic_timers_can_cancel_themselves() {
+        let mut ticker = Ticker::default();
+        let id = ticker
+            .timers
+            .every(10 * MS, Duration::ZERO, |ticker: &mut Ticker| {
+                ticker.fired.push("a");
+                assert!(ticker.timers.cancel(ticker.ids["a"]));
+                // Cancelling twice while running still only cancels once
+                assert!(!ticker.timers.cancel(ticker.ids["a"]));
+
+                Ok(())
+            });
+        ticker.ids.insert("a", id);
+
+        let now = Instant::now() + 20 * MS;
+        run_due_timers(&mut ticker, now, Ticker::timers).unwrap();
+        run_due_timers(&mut ticker, now + 20 * MS, Ticker::timers).unwrap();
+
+        assert_eq!(ticker.fired, ["a"]);
+        assert_eq!(ticker.timers.next_due(), None);
+    }
+
+    #[test]
+    fn callbacks_can_cancel_timers_due_in_the_same_run() {
+        let mut ticker = Ticker::default();
+        for (name, other) in [("a", "b"), ("b", "a")] {
+            let id = ticker.timers.after(10 * MS, move |ticker: &mut Ticker| {
+                ticker.fired.push(name);
+                assert!(ticker.timers.cancel(ticker.ids[other]));
+
+                Ok(())
+            });
+            ticker.ids.insert(name, id);
+        }
+
+        run_due_timers(&mut ticker, Instant::now() + 20 * MS, Ticker::timers).unwrap();
+
+        // Whichever ran first cancelled the other
+        assert_eq!(ticker.fired.len(), 1);
+        assert_eq!(ticker.timers.next_due(), None);
+    }
+
+    #[test]
+    fn callbacks_can_replace_their_own_timer() {
+        let mut ticker = Ticker::default();
+        let id = ticker.timers.after(10 * MS, |ticker: &mut Ticker| {
+            ticker.fired.push("a");
+            // As gossip does: cancelling a one-shot that's running is harmless
+            ticker.timers.cancel(ticker.ids["a"]);
+            let id = ticker.timers.after(30 * MS, fire("b"));
+            ticker.ids.insert("b", id);
+
+            Ok(())
+        });
+        ticker.ids.insert("a", id);
+
+        let now = Instant::now() + 20 * MS;
+        run_due_timers(&mut ticker, now, Ticker::timers).unwrap();
+        assert_eq!(ticker.fired, ["a"]);
+        assert!(ticker.timers.next_due().is_some());
+
+        run_due_timers(&mut ticker, now + 40 * MS, Ticker::timers).unwrap();
+        assert_eq!(ticker.fired, ["a", "b"]);
+        assert_eq!(ticker.timers.next_due(), None);
+    }
+
+    #[test]
+    fn errors_still_reschedule_periodic_timers() {
+        let mut ticker = Ticker::default();
+        ticker
+            .timers
+            .every(10 * MS, Duration::ZERO, |_: &mut Ticker| {
+                Err(color_eyre::eyre::eyre!("boom"))
+            });
+
+        assert!(run_due_timers(&mut ticker, Instant::now() + 20 * MS, Ticker::timers).is_err());
+        assert!(ticker.timers.next_due().is_some());
+    }
+}
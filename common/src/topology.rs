@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+/// The grid topology Maelstrom hands out by default
+///
+/// `node_ids` are laid out row by row in a square grid, in the order given, and each node is
+/// connected to the nodes above, below and beside it.
+pub fn grid_topology(node_ids: &[String]) -> HashMap<String, Vec<String>> {
+    let n = node_ids.len();
+    let width = (n as f64).sqrt().ceil().max(1.0) as usize;
+
+    node_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| {
+            let mut neighbours = vec![];
+            if i % width > 0 {
+                neighbours.push(i - 1);
+            }
+            if i % width + 1 < width && i + 1 < n {
+                neighbours.push(i + 1);
+            }
+            if i >= width {
+                neighbours.push(i - width);
+            }
+            if i + width < n {
+                neighbours.push(i + width);
+            }
+
+            let neighbours = neighbours
+                .into_iter()
+                .map(|j| node_ids[j].clone())
+                .collect();
+            (id.clone(), neighbours)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(n: usize) -> Vec<String> {
+        (0..n).map(|i| format!("n{i}")).collect()
+    }
+
+    #[test]
+    fn grid_of_nine() {
+        let topology = grid_topology(&ids(9));
+
+        assert_eq!(topology["n0"], ["n1", "n3"]);
+        assert_eq!(topology["n4"], ["n3", "n5", "n1", "n7"]);
+        assert_eq!(topology["n8"], ["n7", "n5"]);
+    }
+
+    #[test]
+    fn ragged_grids_are_symmetric() {
+        for n in 1..20 {
+            let topology = grid_topology(&ids(n));
+
+            assert_eq!(topology.len(), n);
+            for (id, neighbours) in &topology {
+                for neighbour in neighbours {
+                    assert!(topology[neighbour].contains(id), "{n}: {id} -> {neighbour}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The `echo` workload: every `echo` is answered with the same text
+
+use color_eyre::eyre::Result;
+use common::*;
+
+#[request(reply = EchoReply)]
+pub enum EchoRequest {
+    Echo { echo: String },
+}
+
+#[reply]
+pub enum EchoReply {
+    Echo { echo: String },
+}
+
+pub struct Echo;
+
+impl Service for Echo {
+    type Request = EchoRequest;
+
+    fn handle(
+        &mut self,
+        node: &Node,
+        request: &Body<EchoRequest>,
+    ) -> Result<Option<Body<EchoReply>>> {
+        let EchoRequest::Echo { echo } = &request.payload;
+
+        Ok(Some(
+            request.reply(node, EchoReply::Echo { echo: echo.clone() }),
+        ))
+    }
+}
@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+
+set -e
+
+cargo build
+java -jar ~/maelstrom/lib/maelstrom.jar test \
+  -w g-counter \
+  --bin ~/Projects/gossip-glomers/target/debug/g-counter \
+  --node-count 3 \
+  --rate 100 \
+  --time-limit 20 \
+  --nemesis partition
@@ -0,0 +1,98 @@
+use common::*;
+
+use color_eyre::eyre::Result;
+
+/// Each node only ever adds to its own key in seq-kv, so the counter is the sum of every
+/// node's key and CAS conflicts only happen between this node's own requests
+struct CounterNode {
+    inner_node: Node,
+    rpc: Rpc,
+    kv: Kv,
+}
+
+#[request(reply = CounterReply)]
+enum CounterRequest {
+    Add { delta: u64 },
+    Read,
+}
+
+#[reply]
+enum CounterReply {
+    Add,
+    Read { value: u64 },
+}
+
+impl CounterNode {
+    fn add(&self, delta: u64) -> Result<()> {
+        let key = self.node_id();
+
+        loop {
+            let current: u64 = self.kv.read_or(key, 0)?;
+
+            match self.kv.cas(key, current, current + delta, true) {
+                Ok(()) => return Ok(()),
+                // Our read was stale, try again with a fresh one
+                Err(KvError::PreconditionFailed(_)) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn read(&self) -> Result<u64> {
+        // seq-kv may serve us stale values, but never ones older than our own last write
+        let sync_key = format!("sync-{}", self.node_id());
+        self.kv.write(sync_key, self.inner_node.generate_msg_id())?;
+
+        let mut total = 0;
+        for node_id in &self.inner_node.peers {
+            total += self.kv.read_or(node_id, 0)?;
+        }
+
+        Ok(total)
+    }
+}
+
+impl NodeIdable for CounterNode {
+    fn node_id(&self) -> &str {
+        self.inner_node.node_id()
+    }
+}
+
+impl Handler for CounterNode {
+    type RequestBody = Body<CounterRequest>;
+    type ResponseBody = Body<CounterReply>;
+
+    fn rpc(&self) -> Option<Rpc> {
+        Some(self.rpc.clone())
+    }
+
+    fn try_handle_request(
+        &mut self,
+        request: &Body<CounterRequest>,
+    ) -> Result<Option<Body<CounterReply>>> {
+        let payload = match request.payload {
+            CounterRequest::Add { delta } => {
+                self.add(delta)?;
+
+                CounterReply::Add
+            }
+            CounterRequest::Read => CounterReply::Read {
+                value: self.read()?,
+            },
+        };
+
+        Ok(Some(request.reply(&self.inner_node, payload)))
+    }
+}
+
+fn main() -> Result<()> {
+    NodeBuilder::init()?.handle_requests(|node| {
+        let rpc = Rpc::new(&node);
+
+        CounterNode {
+            inner_node: node,
+            kv: Kv::seq(rpc.clone()),
+            rpc,
+        }
+    })
+}
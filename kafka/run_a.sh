@@ -0,0 +1,12 @@
+#!/usr/bin/env bash
+
+set -e
+
+cargo build
+java -jar ~/maelstrom/lib/maelstrom.jar test \
+  -w kafka \
+  --bin ~/Projects/gossip-glomers/target/debug/kafka \
+  --node-count 1 \
+  --concurrency 2n \
+  --time-limit 20 \
+  --rate 1000
@@ -0,0 +1,285 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex},
+};
+
+use common::*;
+
+use color_eyre::eyre::Result;
+use serde::Deserialize;
+
+type Offset = u64;
+
+#[request(reply = KafkaReply)]
+enum KafkaRequest {
+    Send { key: String, msg: u64 },
+    Poll { offsets: HashMap<String, Offset> },
+    CommitOffsets { offsets: HashMap<String, Offset> },
+    ListCommittedOffsets { keys: Vec<String> },
+}
+
+#[reply]
+enum KafkaReply {
+    Send {
+        offset: Offset,
+    },
+    Poll {
+        msgs: HashMap<String, Vec<(Offset, u64)>>,
+    },
+    CommitOffsets,
+    ListCommittedOffsets {
+        offsets: HashMap<String, Offset>,
+    },
+}
+
+#[derive(Deserialize)]
+struct SendOk {
+    offset: Offset,
+}
+
+#[derive(Deserialize)]
+struct PollOk {
+    msgs: HashMap<String, Vec<(Offset, u64)>>,
+}
+
+/// Everything the request threads share
+///
+/// Each key has a leader, picked by hashing the key, which is the only node that allocates
+/// offsets for it and stores its log. Other nodes forward `send`s and `poll`s for the key to
+/// its leader. Offsets are allocated from, and committed offsets stored in, lin-kv.
+struct Kafka {
+    node_id: String,
+    node_ids: Vec<String>,
+    ids: Arc<IdGenerator>,
+    rpc: Rpc,
+    kv: Kv,
+    /// Each key's log has its own lock, so a slow allocation only holds up its own key
+    logs: Mutex<HashMap<String, Arc<Mutex<Log>>>>,
+}
+
+type Log = BTreeMap<Offset, u64>;
+
+impl Kafka {
+    fn leader(&self, key: &str) -> &str {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+
+        &self.node_ids[hasher.finish() as usize % self.node_ids.len()]
+    }
+
+    fn send(&self, key: &str, msg: u64) -> Result<Offset> {
+        let leader = self.leader(key);
+        if leader != self.node_id {
+            // The Rpc fills in the msg_id
+            let SendOk { offset } = self.rpc.call_blocking(
+                leader,
+                Body::new(KafkaRequest::Send {
+                    key: key.to_owned(),
+                    msg,
+                }),
+                CallOptions::default(),
+            )?;
+
+            return Ok(offset);
+        }
+
+        // Hold the key's lock while allocating, so a poll can never see an offset before the
+        // ones allocated ahead of it
+        let log = self.log(key);
+        let mut log = log.lock().unwrap();
+        let offset = self.allocate_offset(key)?;
+        log.insert(offset, msg);
+
+        Ok(offset)
+    }
+
+    fn log(&self, key: &str) -> Arc<Mutex<Log>> {
+        let mut logs = self.logs.lock().unwrap();
+
+        Arc::clone(logs.entry(key.to_owned()).or_default())
+    }
+
+    fn allocate_offset(&self, key: &str) -> Result<Offset> {
+        let kv_key = format!("offset-{key}");
+
+        loop {
+            let next: Offset = self.kv.read_or(&kv_key, 0)?;
+
+            match self.kv.cas(&kv_key, next, next + 1, true) {
+                Ok(()) => return Ok(next),
+                Err(KvError::PreconditionFailed(_)) => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    fn poll(
+        &self,
+        offsets: &HashMap<String, Offset>,
+    ) -> Result<HashMap<String, Vec<(Offset, u64)>>> {
+        let mut by_leader: HashMap<&str, HashMap<String, Offset>> = HashMap::new();
+        for (key, offset) in offsets {
+            by_leader
+                .entry(self.leader(key))
+                .or_default()
+                .insert(key.clone(), *offset);
+        }
+
+        let mut msgs = HashMap::new();
+        for (leader, offsets) in by_leader {
+            if leader == self.node_id {
+                msgs.extend(self.poll_local(&offsets));
+                continue;
+            }
+
+            let reply: PollOk = self.rpc.call_blocking(
+                leader,
+                Body::new(KafkaRequest::Poll { offsets }),
+                CallOptions::default(),
+            )?;
+            msgs.extend(reply.msgs);
+        }
+
+        Ok(msgs)
+    }
+
+    fn poll_local(&self, offsets: &HashMap<String, Offset>) -> HashMap<String, Vec<(Offset, u64)>> {
+        offsets
+            .iter()
+            .filter_map(|(key, offset)| {
+                let log = self.logs.lock().unwrap().get(key).cloned()?;
+                let log = log.lock().unwrap();
+                let msgs = log.range(offset..).map(|(o, m)| (*o, *m)).collect();
+
+                Some((key.clone(), msgs))
+            })
+            .collect()
+    }
+
+    /// Committed offsets only ever move forward, however the commits are ordered
+    fn commit_offsets(&self, offsets: &HashMap<String, Offset>) -> Result<()> {
+        for (key, offset) in offsets {
+            let kv_key = format!("commit-{key}");
+
+            loop {
+                let current: Option<Offset> = self.kv.read_or(&kv_key, None)?;
+                if current.is_some_and(|current| current >= *offset) {
+                    break;
+                }
+
+                match self.kv.cas(&kv_key, current, Some(*offset), true) {
+                    Ok(()) => break,
+                    Err(KvError::PreconditionFailed(_)) => continue,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn list_committed_offsets(&self, keys: &[String]) -> Result<HashMap<String, Offset>> {
+        let mut offsets = HashMap::new();
+
+        for key in keys {
+            let committed: Option<Offset> = self.kv.read_or(format!("commit-{key}"), None)?;
+
+            if let Some(committed) = committed {
+                offsets.insert(key.clone(), committed);
+            }
+        }
+
+        Ok(offsets)
+    }
+
+    fn handle(&self, request: &Body<KafkaRequest>) -> Result<Body<KafkaReply>> {
+        let reply = match &request.payload {
+            KafkaRequest::Send { key, msg } => KafkaReply::Send {
+                offset: self.send(key, *msg)?,
+            },
+            KafkaRequest::Poll { offsets } => KafkaReply::Poll {
+                msgs: self.poll(offsets)?,
+            },
+            KafkaRequest::CommitOffsets { offsets } => {
+                self.commit_offsets(offsets)?;
+
+                KafkaReply::CommitOffsets
+            }
+            KafkaRequest::ListCommittedOffsets { keys } => KafkaReply::ListCommittedOffsets {
+                offsets: self.list_committed_offsets(keys)?,
+            },
+        };
+
+        Ok(request.reply(&*self.ids, reply))
+    }
+}
+
+/// Handles each request on a thread of its own, see [`KafkaNode::handle_lines`]
+#[derive(Clone)]
+struct KafkaNode {
+    kafka: Arc<Kafka>,
+}
+
+impl NodeIdable for KafkaNode {
+    fn node_id(&self) -> &str {
+        &self.kafka.node_id
+    }
+}
+
+impl Handler for KafkaNode {
+    type RequestBody = Body<KafkaRequest>;
+    type ResponseBody = Body<KafkaReply>;
+
+    fn rpc(&self) -> Option<Rpc> {
+        Some(self.kafka.rpc.clone())
+    }
+
+    fn try_handle_request(
+        &mut self,
+        request: &Body<KafkaRequest>,
+    ) -> Result<Option<Body<KafkaReply>>> {
+        self.kafka.handle(request).map(Some)
+    }
+
+    /// Requests can wait on lin-kv or another node, which may in turn be waiting on us, so
+    /// each line goes to [`Handler::handle_line`] on its own thread, and replies from there
+    fn handle_lines(self, lines: impl IntoIterator<Item = String>) -> Result<()> {
+        for line in lines {
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut handler = self.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = handler.handle_line(&line) {
+                    eprintln!("Could not handle {line}: {e}");
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    NodeBuilder::init()?.handle_requests(|node| {
+        let rpc = Rpc::new(&node);
+
+        let mut node_ids = node.peers.clone();
+        node_ids.sort();
+
+        let kafka = Kafka {
+            node_id: node.id.clone(),
+            node_ids,
+            ids: Arc::clone(&node.ids),
+            kv: Kv::lin(rpc.clone()),
+            rpc,
+            logs: Mutex::new(HashMap::new()),
+        };
+
+        KafkaNode {
+            kafka: Arc::new(kafka),
+        }
+    })
+}
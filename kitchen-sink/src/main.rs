@@ -0,0 +1,23 @@
+use broadcast::{configured_strategy, Broadcast, RetryPolicy};
+use common::*;
+use echo::Echo;
+use unique_ids::UniqueIds;
+
+use color_eyre::eyre::Result;
+
+/// One node for every workload it has a service for. Each workload's crate exports its
+/// service, which the workload's own binary runs on its own, and which is registered on the
+/// router here
+fn main() -> Result<()> {
+    let strategy = configured_strategy()?;
+    let retry_policy = RetryPolicy::configured()?;
+
+    NodeBuilder::init()?.run(Runtime::new(), |node| {
+        let broadcast = Broadcast::new(&node, strategy, retry_policy);
+
+        Router::new(node)
+            .with_service(Echo)
+            .with_service(UniqueIds::default())
+            .with_service(broadcast)
+    })
+}
@@ -0,0 +1,276 @@
+//! Attributes for Maelstrom message body enums, re-exported from `common`
+//!
+//! Each variant of a body enum holds only its payload fields. The attributes derive serde
+//! for it, tagged by `type` with each variant's name in snake case, and `common::Body` carries
+//! the `msg_id` and `in_reply_to` alongside it.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{
+    meta::ParseNestedMeta, parse::Nothing, parse_macro_input, parse_quote, token::Paren, Attribute,
+    Data, DeriveInput, Error, Field, Fields, LitStr, Token, Type,
+};
+
+/// The body of a request, paired with the body of its reply if it has one
+///
+/// ```ignore
+/// #[request(reply = EchoReply)]
+/// enum EchoRequest {
+///     Echo { echo: String },
+/// }
+/// ```
+///
+/// `Echo` is sent as `{"type": "echo", "echo": ...}`, and `Body<EchoRequest>::reply` only
+/// takes an `EchoReply`.
+#[proc_macro_attribute]
+pub fn request(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut reply: Option<Type> = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("reply") {
+            reply = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `reply = <type>`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+
+    let mut input = parse_macro_input!(input as DeriveInput);
+    let payload = match payload(&mut input, "") {
+        Ok(payload) => payload,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // Requests nobody replies to, like gossip between nodes, needn't be paired
+    let Some(reply) = reply else {
+        return payload.into();
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        #payload
+
+        impl #impl_generics ::common::Paired for #name #ty_generics #where_clause {
+            type Reply = #reply;
+        }
+    }
+    .into()
+}
+
+/// The body of a reply, with each variant sent as its name in snake case plus `_ok`
+#[proc_macro_attribute]
+pub fn reply(args: TokenStream, input: TokenStream) -> TokenStream {
+    parse_macro_input!(args as Nothing);
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    payload(&mut input, "_ok")
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// `input` deriving serde tagged by `type`, with every variant that hasn't been renamed
+/// already named, and its `common::Payload` and `common::Kinds` impls
+///
+/// `Payload::fields` lists the fields each variant reads, so `common::Body` can tell which
+/// ones are unknown. serde buffers an internally tagged enum's fields before the variant
+/// reads them, so it can't be asked which ones went unread.
+fn payload(input: &mut DeriveInput, suffix: &str) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Enum(data) = &mut input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "message bodies must be enums",
+        ));
+    };
+
+    let mut kinds = vec![];
+    let mut arms = vec![];
+    let mut field_arms = vec![];
+    for variant in &mut data.variants {
+        let kind = match variant.attrs.iter().find_map(rename) {
+            Some(kind) => kind,
+            None => {
+                let kind = format!("{}{suffix}", snake_case(&variant.ident.to_string()));
+                variant.attrs.push(parse_quote!(#[serde(rename = #kind)]));
+
+                kind
+            }
+        };
+
+        let fields = match variant_fields(&variant.fields)? {
+            Some(names) => quote!(::core::option::Option::Some(&[#(#names),*])),
+            None => quote!(::core::option::Option::None),
+        };
+        field_arms.push(quote!(#kind => #fields));
+
+        let ident = &variant.ident;
+        arms.push(quote!(Self::#ident { .. } => #kind));
+        kinds.push(kind);
+    }
+
+    input.attrs.insert(
+        0,
+        parse_quote!(#[derive(::serde::Serialize, ::serde::Deserialize, Debug, Clone)]),
+    );
+    input.attrs.insert(1, parse_quote!(#[serde(tag = "type")]));
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        #input
+
+        impl #impl_generics ::common::Payload for #name #ty_generics #where_clause {
+            fn kind(&self) -> &str {
+                match *self {
+                    #(#arms,)*
+                }
+            }
+
+            fn fields(kind: &str) -> ::core::option::Option<&'static [&'static str]> {
+                match kind {
+                    #(#field_arms,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+
+        impl #impl_generics ::common::Kinds for #name #ty_generics #where_clause {
+            const KINDS: &'static [&'static str] = &[#(#kinds),*];
+        }
+    })
+}
+
+/// The name a variant's `#[serde(rename = "...")]` gives it
+fn rename(attr: &Attribute) -> Option<String> {
+    if !attr.path().is_ident("serde") {
+        return None;
+    }
+
+    let mut renamed = None;
+    // Anything we can't parse is left for serde to complain about
+    let _ = attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") {
+            renamed = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else if meta.input.peek(Token![=]) {
+            meta.value()?.parse::<syn::Lit>()?;
+        }
+
+        Ok(())
+    });
+
+    renamed
+}
+
+/// Every name a variant reads a field by, or `None` if it could read any field, as a
+/// flattened field or a tuple variant's payload can
+fn variant_fields(fields: &Fields) -> syn::Result<Option<Vec<String>>> {
+    let Fields::Named(fields) = fields else {
+        return Ok(matches!(fields, Fields::Unit).then(Vec::new));
+    };
+
+    let mut names = vec![];
+    for field in &fields.named {
+        match field_names(field)? {
+            Some(field_names) => names.extend(field_names),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(names))
+}
+
+/// The names serde reads `field` by: its own or renamed name, plus any aliases. Skipped
+/// fields aren't read at all, and flattened ones (`None`) read whatever is left over
+fn field_names(field: &Field) -> syn::Result<Option<Vec<String>>> {
+    let mut name = field
+        .ident
+        .as_ref()
+        .map(|ident| ident.to_string().trim_start_matches("r#").to_owned())
+        .unwrap_or_default();
+    let mut aliases = vec![];
+    let mut skipped = false;
+    let mut flattened = false;
+
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("serde"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                if let Some(renamed) = deserialize_name(&meta)? {
+                    name = renamed;
+                }
+            } else if meta.path.is_ident("alias") {
+                aliases.push(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+                skipped = true;
+            } else if meta.path.is_ident("flatten") {
+                flattened = true;
+            } else {
+                skip_meta(&meta)?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if flattened {
+        return Ok(None);
+    }
+    if skipped {
+        return Ok(Some(vec![]));
+    }
+
+    Ok(Some(std::iter::once(name).chain(aliases).collect()))
+}
+
+/// The name from `rename = "..."`, or the `deserialize` one from
+/// `rename(serialize = "...", deserialize = "...")`
+fn deserialize_name(meta: &ParseNestedMeta) -> syn::Result<Option<String>> {
+    if meta.input.peek(Token![=]) {
+        return Ok(Some(meta.value()?.parse::<LitStr>()?.value()));
+    }
+
+    let mut name = None;
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("deserialize") {
+            name = Some(meta.value()?.parse::<LitStr>()?.value());
+        } else {
+            skip_meta(&meta)?;
+        }
+
+        Ok(())
+    })?;
+
+    Ok(name)
+}
+
+/// Step over a serde option we don't care about, whatever its shape
+fn skip_meta(meta: &ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(Paren) {
+        meta.parse_nested_meta(|meta| skip_meta(&meta))?;
+    }
+
+    Ok(())
+}
+
+/// `GossipOk` becomes `gossip_ok`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+
+    snake
+}
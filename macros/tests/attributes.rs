@@ -0,0 +1,128 @@
+//! What `#[request]` and `#[reply]` generate, seen through `common`'s traits and serde
+
+use common::{reply, request, Body, Kinds, Paired, Payload};
+use serde_json::{json, Map, Value};
+
+#[request(reply = ExampleReply)]
+enum ExampleRequest {
+    Echo {
+        echo: String,
+    },
+    GossipOk,
+    #[serde(rename = "txn_rw")]
+    Txn,
+}
+
+#[reply]
+enum ExampleReply {
+    Echo {
+        echo: String,
+    },
+    ListCommittedOffsets,
+    #[serde(rename = "done")]
+    Finished,
+}
+
+#[test]
+fn variants_are_named_in_snake_case() {
+    let echo = ExampleRequest::Echo {
+        echo: "hi".to_owned(),
+    };
+
+    assert_eq!(echo.kind(), "echo");
+    assert_eq!(ExampleRequest::GossipOk.kind(), "gossip_ok");
+    assert_eq!(
+        serde_json::to_value(&echo).unwrap(),
+        json!({"type": "echo", "echo": "hi"})
+    );
+}
+
+#[test]
+fn replies_are_suffixed_with_ok() {
+    let echo = ExampleReply::Echo {
+        echo: "hi".to_owned(),
+    };
+
+    assert_eq!(
+        serde_json::to_value(&echo).unwrap(),
+        json!({"type": "echo_ok", "echo": "hi"})
+    );
+    assert_eq!(
+        ExampleReply::ListCommittedOffsets.kind(),
+        "list_committed_offsets_ok"
+    );
+}
+
+#[test]
+fn explicit_renames_are_kept() {
+    assert_eq!(ExampleRequest::Txn.kind(), "txn_rw");
+    assert_eq!(ExampleReply::Finished.kind(), "done");
+
+    let body: Body<ExampleRequest> =
+        serde_json::from_value(json!({"type": "txn_rw", "msg_id": 1})).unwrap();
+    assert!(matches!(body.payload, ExampleRequest::Txn));
+}
+
+#[test]
+fn kinds_lists_every_variant() {
+    assert_eq!(ExampleRequest::KINDS, ["echo", "gossip_ok", "txn_rw"]);
+    assert_eq!(
+        ExampleReply::KINDS,
+        ["echo_ok", "list_committed_offsets_ok", "done"]
+    );
+}
+
+#[test]
+fn requests_are_paired_with_their_reply() {
+    fn reply_kinds<P: Paired>() -> &'static [&'static str]
+    where
+        P::Reply: Kinds,
+    {
+        P::Reply::KINDS
+    }
+
+    assert_eq!(reply_kinds::<ExampleRequest>(), ExampleReply::KINDS);
+}
+
+#[request]
+enum Fielded {
+    Read {
+        #[serde(rename = "from")]
+        since: u64,
+        #[serde(alias = "max", default)]
+        limit: Option<u64>,
+        #[serde(skip)]
+        cursor: u64,
+    },
+    Flattened {
+        #[serde(flatten)]
+        rest: Map<String, Value>,
+    },
+    Tuple(Value),
+    Unit,
+}
+
+#[test]
+fn fields_lists_the_names_each_variant_reads() {
+    assert_eq!(Fielded::fields("read"), Some(&["from", "limit", "max"][..]));
+    assert_eq!(Fielded::fields("unit"), Some(&[][..]));
+    assert_eq!(Fielded::fields("flattened"), None);
+    assert_eq!(Fielded::fields("tuple"), None);
+    assert_eq!(Fielded::fields("unknown"), None);
+}
+
+#[test]
+fn bodies_keep_only_the_fields_nobody_reads() {
+    let body: Body<Fielded> = serde_json::from_value(
+        json!({"type": "read", "msg_id": 1, "from": 2, "max": 3, "cursor": 4, "trace": "a"}),
+    )
+    .unwrap();
+
+    assert_eq!(
+        body.extra,
+        json!({"cursor": 4, "trace": "a"})
+            .as_object()
+            .cloned()
+            .unwrap()
+    );
+}
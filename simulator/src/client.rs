@@ -0,0 +1,74 @@
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{bail, Result};
+use common::{Message, MsgId};
+use crossbeam::channel::{Receiver, Sender};
+use serde_json::Value;
+
+/// A Maelstrom client attached to a [`crate::Network`]
+///
+/// Clients are never partitioned and never lose messages, only nodes are.
+pub struct Client {
+    id: String,
+    outbox: Sender<String>,
+    inbox: Receiver<Message<Value>>,
+    next_msg_id: MsgId,
+}
+
+impl Client {
+    pub(crate) fn new(id: String, outbox: Sender<String>, inbox: Receiver<Message<Value>>) -> Self {
+        Self {
+            id,
+            outbox,
+            inbox,
+            next_msg_id: 1,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Send `body` to `dest` with a fresh `msg_id`, without waiting for a reply
+    pub fn send(&mut self, dest: &str, mut body: Value) -> Result<MsgId> {
+        let msg_id = self.next_msg_id;
+        self.next_msg_id += 1;
+
+        let Some(fields) = body.as_object_mut() else {
+            bail!("Message bodies must be JSON objects, got {body}");
+        };
+        fields.insert("msg_id".to_owned(), msg_id.into());
+
+        let m = Message::new(&self.id, dest, body);
+        self.outbox.send(serde_json::to_string(&m)?)?;
+
+        Ok(msg_id)
+    }
+
+    /// Send `body` to `dest` and wait for the body of its reply
+    ///
+    /// `error` replies are returned like any other reply. Replies to earlier requests that
+    /// timed out are discarded.
+    pub fn request(&mut self, dest: &str, body: Value, timeout: Duration) -> Result<Value> {
+        let msg_id = self.send(dest, body)?;
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let Ok(m) = self.inbox.recv_deadline(deadline) else {
+                bail!(
+                    "{} timed out waiting for {dest} to reply to {msg_id}",
+                    self.id
+                );
+            };
+
+            if m.body["in_reply_to"].as_u64() == Some(msg_id) {
+                return Ok(m.body);
+            }
+
+            eprintln!(
+                "{} discarding stale reply from {}: {}",
+                self.id, m.src, m.body
+            );
+        }
+    }
+}
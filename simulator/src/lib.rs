@@ -0,0 +1,395 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{bail, eyre, Result};
+use common::{Handler, MemoryKv, Message, Outbox, LIN_KV, LWW_KV, SEQ_KV};
+use crossbeam::channel::{unbounded, Receiver, RecvTimeoutError, Sender};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+mod client;
+pub use client::*;
+
+mod run;
+pub use run::*;
+
+mod workload;
+pub use workload::*;
+
+#[derive(Debug, Clone)]
+pub struct NetworkConfig {
+    /// How long every message spends in flight
+    pub latency: Duration,
+    /// Probability that a message between two nodes is dropped
+    pub loss: f64,
+    /// Pass the node binaries' stderr through instead of discarding it
+    pub log_stderr: bool,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::ZERO,
+            loss: 0.0,
+            log_stderr: false,
+        }
+    }
+}
+
+/// A message as it was put on the network, one per line in a recorded history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Event {
+    /// Microseconds since the network was created. Missing from captures of plain node
+    /// stdio, which have no timings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<u64>,
+    #[serde(flatten)]
+    pub message: Message<Value>,
+}
+
+enum Route {
+    Process(ChildStdin),
+    InProcess(Sender<String>),
+    Client(Sender<Message<Value>>),
+}
+
+#[derive(Default)]
+struct Shared {
+    routes: HashMap<String, Route>,
+    node_ids: Vec<String>,
+    /// Which side of the current partition each node is on. Empty when the network is healed
+    partition: HashMap<String, usize>,
+    history: Vec<Event>,
+}
+
+impl Shared {
+    fn is_node(&self, id: &str) -> bool {
+        self.node_ids.iter().any(|n| n == id)
+    }
+
+    fn is_partitioned(&self, src: &str, dest: &str) -> bool {
+        match (self.partition.get(src), self.partition.get(dest)) {
+            (Some(a), Some(b)) => a != b,
+            _ => false,
+        }
+    }
+}
+
+/// An in-process stand-in for Maelstrom's network
+///
+/// Nodes are either binaries, whose stdin and stdout get wired up to the network, or closures
+/// running on their own thread that receive JSON lines on a channel and send JSON lines back.
+/// Every line a node prints is routed to the node, service or client named in its `dest`.
+pub struct Network {
+    shared: Arc<Mutex<Shared>>,
+    outbox: Sender<String>,
+    children: Vec<Child>,
+    log_stderr: bool,
+    next_client: u64,
+}
+
+impl Network {
+    pub fn new(config: NetworkConfig) -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let (outbox, receiver) = unbounded();
+
+        let log_stderr = config.log_stderr;
+        let router_shared = Arc::clone(&shared);
+        std::thread::spawn(move || route(receiver, router_shared, config));
+
+        Self {
+            shared,
+            outbox,
+            children: vec![],
+            log_stderr,
+            next_client: 1,
+        }
+    }
+
+    /// Spawn `bin` as a node called `id`
+    pub fn add_binary(&mut self, id: &str, bin: impl AsRef<Path>) -> Result<()> {
+        let stderr = if self.log_stderr {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        };
+        let mut child = Command::new(bin.as_ref())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(stderr)
+            .spawn()?;
+
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| eyre!("No stdin for {id}"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| eyre!("No stdout for {id}"))?;
+
+        let outbox = self.outbox.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+
+                if outbox.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.children.push(child);
+        self.register_node(id, Route::Process(stdin));
+
+        Ok(())
+    }
+
+    /// Spawn `count` copies of `bin`, named `n1`, `n2`, ... after any nodes already added
+    pub fn add_binaries(&mut self, bin: impl AsRef<Path>, count: usize) -> Result<Vec<String>> {
+        let first = self.node_ids().len() + 1;
+        let ids: Vec<String> = (first..first + count).map(|i| format!("n{i}")).collect();
+
+        for id in &ids {
+            self.add_binary(id, bin.as_ref())?;
+        }
+
+        Ok(ids)
+    }
+
+    /// Run `node` on its own thread as a node called `id`
+    ///
+    /// It receives one JSON message per line on the first channel and sends its own on the
+    /// second. It should return once the receiving channel is closed.
+    pub fn add_node<F>(&mut self, id: &str, node: F)
+    where
+        F: FnOnce(Receiver<String>, Sender<String>) + Send + 'static,
+    {
+        let inbox = self.spawn_in_process(node);
+        self.register_node(id, Route::InProcess(inbox));
+    }
+
+    /// Like [`Network::add_node`], but for a Maelstrom service such as `seq-kv`
+    ///
+    /// Services don't receive an `init` message, aren't part of `node_ids` and are never
+    /// partitioned or subject to message loss.
+    pub fn add_service<F>(&mut self, id: &str, service: F)
+    where
+        F: FnOnce(Receiver<String>, Sender<String>) + Send + 'static,
+    {
+        let inbox = self.spawn_in_process(service);
+        self.lock()
+            .routes
+            .insert(id.to_owned(), Route::InProcess(inbox));
+    }
+
+    /// Add in-memory stand-ins for `seq-kv`, `lin-kv` and `lww-kv`
+    pub fn add_kv_services(&mut self) {
+        let log_messages = self.log_stderr;
+
+        for service in [SEQ_KV, LIN_KV, LWW_KV] {
+            self.add_service(service, move |inbox, outbox| {
+                let kv = MemoryKv::new(service, Outbox::Channel(outbox)).with_logging(log_messages);
+
+                if let Err(e) = kv.handle_lines(inbox) {
+                    eprintln!("{service} stopped: {e}");
+                }
+            });
+        }
+    }
+
+    fn spawn_in_process<F>(&self, f: F) -> Sender<String>
+    where
+        F: FnOnce(Receiver<String>, Sender<String>) + Send + 'static,
+    {
+        let (inbox, receiver) = unbounded();
+        let outbox = self.outbox.clone();
+        std::thread::spawn(move || f(receiver, outbox));
+
+        inbox
+    }
+
+    fn register_node(&self, id: &str, route: Route) {
+        let mut shared = self.lock();
+        shared.routes.insert(id.to_owned(), route);
+        shared.node_ids.push(id.to_owned());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap()
+    }
+
+    pub fn node_ids(&self) -> Vec<String> {
+        self.lock().node_ids.clone()
+    }
+
+    /// Create a new client, named `c1`, `c2`, ...
+    pub fn client(&mut self) -> Client {
+        let id = format!("c{}", self.next_client);
+        self.next_client += 1;
+
+        let (sender, receiver) = unbounded();
+        self.lock().routes.insert(id.clone(), Route::Client(sender));
+
+        Client::new(id, self.outbox.clone(), receiver)
+    }
+
+    /// Send every node its `init` message and wait for all of them to reply
+    pub fn init(&mut self) -> Result<()> {
+        let node_ids = self.node_ids();
+        let mut client = self.client();
+
+        for node_id in &node_ids {
+            let reply = client.request(
+                node_id,
+                json!({
+                    "type": "init",
+                    "node_id": node_id,
+                    "node_ids": node_ids,
+                }),
+                Duration::from_secs(5),
+            )?;
+
+            if reply["type"] != "init_ok" {
+                bail!("{node_id} replied to init with {reply}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Split the nodes into the given groups. Messages between groups are dropped until
+    /// [`Network::heal`] is called. Nodes not in any group can still talk to everyone.
+    pub fn partition(&self, groups: &[Vec<String>]) {
+        let mut shared = self.lock();
+        shared.partition = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(i, group)| group.iter().map(move |id| (id.clone(), i)))
+            .collect();
+    }
+
+    pub fn heal(&self) {
+        self.lock().partition.clear();
+    }
+
+    /// Every message sent so far, in the order they were put on the network
+    pub fn history(&self) -> Vec<Event> {
+        self.lock().history.clone()
+    }
+
+    /// Write [`Network::history`] to `path` as JSONL
+    pub fn write_history(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        for event in self.history() {
+            writeln!(file, "{}", serde_json::to_string(&event)?)?;
+        }
+        file.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        // Closing every stdin and inbox lets well behaved nodes exit on their own
+        self.lock().routes.clear();
+
+        for child in &mut self.children {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn route(receiver: Receiver<String>, shared: Arc<Mutex<Shared>>, config: NetworkConfig) {
+    let started = Instant::now();
+    let mut next_seq: u64 = 0;
+    let mut in_flight: BinaryHeap<Reverse<(Instant, u64)>> = BinaryHeap::new();
+    let mut messages: HashMap<u64, Message<Value>> = HashMap::new();
+
+    loop {
+        let received = match in_flight.peek() {
+            Some(Reverse((deliver_at, _))) => receiver.recv_deadline(*deliver_at),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        match received {
+            Ok(line) => {
+                let m = match serde_json::from_str::<Message<Value>>(&line) {
+                    Ok(m) => m,
+                    Err(e) => {
+                        eprintln!("Dropping unparseable message {line}: {e}");
+                        continue;
+                    }
+                };
+
+                let mut shared = shared.lock().unwrap();
+                shared.history.push(Event {
+                    time: Some(started.elapsed().as_micros() as u64),
+                    message: m.clone(),
+                });
+
+                let between_nodes = shared.is_node(&m.src) && shared.is_node(&m.dest);
+                if shared.is_partitioned(&m.src, &m.dest)
+                    || (between_nodes && rand::thread_rng().gen_bool(config.loss))
+                {
+                    continue;
+                }
+
+                in_flight.push(Reverse((Instant::now() + config.latency, next_seq)));
+                messages.insert(next_seq, m);
+                next_seq += 1;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let now = Instant::now();
+        while let Some(Reverse((deliver_at, seq))) = in_flight.peek().copied() {
+            if deliver_at > now {
+                break;
+            }
+            in_flight.pop();
+
+            let Some(m) = messages.remove(&seq) else {
+                continue;
+            };
+            deliver(&mut shared.lock().unwrap(), m);
+        }
+    }
+}
+
+fn deliver(shared: &mut Shared, m: Message<Value>) {
+    let Some(route) = shared.routes.get_mut(&m.dest) else {
+        eprintln!("Dropping message to unknown destination {}", m.dest);
+        return;
+    };
+
+    let delivered = match route {
+        Route::Process(stdin) => serde_json::to_string(&m)
+            .map_err(|e| e.to_string())
+            .and_then(|line| {
+                writeln!(stdin, "{line}")
+                    .and_then(|_| stdin.flush())
+                    .map_err(|e| e.to_string())
+            }),
+        Route::InProcess(inbox) => serde_json::to_string(&m)
+            .map_err(|e| e.to_string())
+            .and_then(|line| inbox.send(line).map_err(|e| e.to_string())),
+        Route::Client(inbox) => inbox.send(m.clone()).map_err(|e| e.to_string()),
+    };
+
+    if let Err(e) = delivered {
+        eprintln!("Could not deliver message to {}: {e}", m.dest);
+    }
+}
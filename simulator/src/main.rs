@@ -0,0 +1,62 @@
+use std::{path::PathBuf, time::Duration};
+
+use color_eyre::eyre::{bail, eyre, Result};
+use simulator::Run;
+
+/// Mirrors the `maelstrom test` flags used by the `run*.sh` scripts
+struct Opts {
+    run: Run,
+    history: Option<PathBuf>,
+}
+
+impl Opts {
+    fn parse() -> Result<Self> {
+        let mut workload = None;
+        let mut bin = None;
+        let mut run = Run::new("", "");
+        let mut history = None;
+
+        let mut args = std::env::args().skip(1);
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| eyre!("{flag} needs a value"));
+
+            match flag.as_str() {
+                "-w" | "--workload" => workload = Some(value()?),
+                "--bin" => bin = Some(PathBuf::from(value()?)),
+                "--node-count" => run.node_count = value()?.parse()?,
+                "--time-limit" => run.time_limit = Duration::from_secs(value()?.parse()?),
+                "--rate" => run.rate = value()?.parse()?,
+                "--latency" => run.network.latency = Duration::from_millis(value()?.parse()?),
+                "--loss" => run.network.loss = value()?.parse()?,
+                "--nemesis" => match value()?.as_str() {
+                    "partition" => run.partition_every = Some(Duration::from_secs(5)),
+                    other => bail!("Unknown nemesis {other}"),
+                },
+                "--history" => history = Some(PathBuf::from(value()?)),
+                "--log-stderr" => run.network.log_stderr = true,
+                other => bail!("Unknown flag {other}"),
+            }
+        }
+
+        run.workload = workload.ok_or_else(|| eyre!("--workload is required"))?;
+        run.bin = bin.ok_or_else(|| eyre!("--bin is required"))?;
+
+        Ok(Opts { run, history })
+    }
+}
+
+fn main() -> Result<()> {
+    let opts = Opts::parse()?;
+
+    let net = opts.run.run()?;
+
+    let history = net.history();
+    println!("{} messages sent", history.len());
+
+    if let Some(path) = &opts.history {
+        net.write_history(path)?;
+        println!("History written to {}", path.display());
+    }
+
+    Ok(())
+}
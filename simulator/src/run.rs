@@ -0,0 +1,121 @@
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{Network, NetworkConfig, Workload};
+
+/// One run of a workload against a node binary, like a single `maelstrom test`
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub workload: String,
+    pub bin: PathBuf,
+    pub node_count: usize,
+    pub time_limit: Duration,
+    /// Client operations per second, across every client
+    pub rate: f64,
+    pub network: NetworkConfig,
+    /// Partition the nodes in two for this long, then heal them for as long, over and over
+    pub partition_every: Option<Duration>,
+    /// How long the nodes get to converge, once healed, before the final ops
+    pub settle: Duration,
+}
+
+impl Run {
+    pub fn new(workload: impl Into<String>, bin: impl Into<PathBuf>) -> Self {
+        Self {
+            workload: workload.into(),
+            bin: bin.into(),
+            node_count: 1,
+            time_limit: Duration::from_secs(10),
+            rate: 10.0,
+            network: NetworkConfig::default(),
+            partition_every: None,
+            settle: Duration::from_secs(5),
+        }
+    }
+
+    /// Run the workload, returning the network so its history can be looked at
+    pub fn run(&self) -> Result<Network> {
+        let workload = Workload::from_name(&self.workload)?;
+
+        let mut net = Network::new(self.network.clone());
+        net.add_kv_services();
+        let node_ids = net.add_binaries(&self.bin, self.node_count)?;
+        net.init()?;
+
+        let mut setup_client = net.client();
+        workload.setup(&net, &mut setup_client)?;
+
+        // Same as Maelstrom: twice as many clients as nodes, spread evenly over the nodes
+        let concurrency = 2 * node_ids.len();
+        let mut clients: Vec<_> = (0..concurrency).map(|_| net.client()).collect();
+        let interval = Duration::from_secs_f64(concurrency as f64 / self.rate);
+        let deadline = Instant::now() + self.time_limit;
+        let timeout = Duration::from_secs(5);
+
+        std::thread::scope(|s| {
+            for (i, client) in clients.iter_mut().enumerate() {
+                let node_id = &node_ids[i % node_ids.len()];
+                let workload = &workload;
+
+                s.spawn(move || {
+                    while Instant::now() < deadline {
+                        if let Err(e) = client.request(node_id, workload.next_op(), timeout) {
+                            eprintln!("{e}");
+                        }
+
+                        let pause = rand::thread_rng().gen_range(Duration::ZERO..interval * 2);
+                        std::thread::sleep(pause);
+                    }
+                });
+            }
+
+            if let Some(period) = self.partition_every {
+                let net = &net;
+                let node_ids = &node_ids;
+
+                s.spawn(move || {
+                    while Instant::now() + period < deadline {
+                        std::thread::sleep(period);
+
+                        let mut shuffled = node_ids.clone();
+                        shuffled.shuffle(&mut rand::thread_rng());
+                        let split = shuffled.len().div_ceil(2);
+                        let (a, b) = shuffled.split_at(split);
+                        eprintln!("Partitioning {a:?} from {b:?}");
+                        net.partition(&[a.to_vec(), b.to_vec()]);
+
+                        std::thread::sleep(
+                            period.min(deadline.saturating_duration_since(Instant::now())),
+                        );
+                        eprintln!("Healing partition");
+                        net.heal();
+                    }
+                });
+            }
+        });
+
+        net.heal();
+
+        let final_ops = workload.final_ops();
+        if !final_ops.is_empty() {
+            // Give the nodes a chance to converge before the final reads
+            std::thread::sleep(self.settle);
+
+            let mut final_client = net.client();
+            for node_id in &node_ids {
+                for op in &final_ops {
+                    if let Err(e) = final_client.request(node_id, op.clone(), timeout) {
+                        eprintln!("{e}");
+                    }
+                }
+            }
+        }
+
+        Ok(net)
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{Client, Network};
+use color_eyre::eyre::{bail, Result};
+use common::grid_topology;
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::{json, Value};
+
+pub enum Workload {
+    Echo,
+    UniqueIds,
+    Broadcast { next_value: AtomicU64 },
+    GCounter,
+    Kafka { next_value: AtomicU64 },
+    TxnRwRegister,
+}
+
+impl Workload {
+    pub fn from_name(name: &str) -> Result<Self> {
+        Ok(match name {
+            "echo" => Workload::Echo,
+            "unique-ids" => Workload::UniqueIds,
+            "broadcast" => Workload::Broadcast {
+                next_value: AtomicU64::new(0),
+            },
+            "g-counter" => Workload::GCounter,
+            "kafka" => Workload::Kafka {
+                next_value: AtomicU64::new(0),
+            },
+            "txn-rw-register" => Workload::TxnRwRegister,
+            other => bail!("Unknown workload {other}"),
+        })
+    }
+
+    /// Anything that has to happen between `init` and the first client operation
+    pub fn setup(&self, net: &Network, client: &mut Client) -> Result<()> {
+        if let Workload::Broadcast { .. } = self {
+            let topology = grid_topology(&net.node_ids());
+
+            for node_id in net.node_ids() {
+                client.request(
+                    &node_id,
+                    json!({ "type": "topology", "topology": topology }),
+                    Duration::from_secs(5),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The body of the next client operation
+    pub fn next_op(&self) -> Value {
+        let mut rng = rand::thread_rng();
+
+        match self {
+            Workload::Echo => {
+                let echo: String = (0..16).map(|_| rng.sample(Alphanumeric) as char).collect();
+                json!({ "type": "echo", "echo": format!("Please echo {echo}") })
+            }
+            Workload::UniqueIds => json!({ "type": "generate" }),
+            Workload::Broadcast { next_value } => {
+                if rng.gen_bool(0.5) {
+                    let message = next_value.fetch_add(1, Ordering::SeqCst);
+                    json!({ "type": "broadcast", "message": message })
+                } else {
+                    json!({ "type": "read" })
+                }
+            }
+            Workload::GCounter => {
+                if rng.gen_bool(0.5) {
+                    json!({ "type": "add", "delta": rng.gen_range(0..5) })
+                } else {
+                    json!({ "type": "read" })
+                }
+            }
+            Workload::Kafka { next_value } => {
+                let key = format!("{}", rng.gen_range(0..5));
+                let offset = rng.gen_range(0..10);
+
+                match rng.gen_range(0..4) {
+                    0 | 1 => {
+                        let msg = next_value.fetch_add(1, Ordering::SeqCst);
+                        json!({ "type": "send", "key": key, "msg": msg })
+                    }
+                    2 => json!({ "type": "poll", "offsets": { key: offset } }),
+                    _ => json!({ "type": "commit_offsets", "offsets": { key: offset } }),
+                }
+            }
+            Workload::TxnRwRegister => {
+                let txn: Vec<Value> = (0..rng.gen_range(1..4))
+                    .map(|_| {
+                        let key = rng.gen_range(0..10);
+                        if rng.gen_bool(0.5) {
+                            json!(["r", key, null])
+                        } else {
+                            json!(["w", key, rng.gen_range(0..100)])
+                        }
+                    })
+                    .collect();
+
+                json!({ "type": "txn", "txn": txn })
+            }
+        }
+    }
+
+    /// Operations to run against every node once the network has healed and settled
+    pub fn final_ops(&self) -> Vec<Value> {
+        match self {
+            Workload::Broadcast { .. } | Workload::GCounter => vec![json!({ "type": "read" })],
+            _ => vec![],
+        }
+    }
+}
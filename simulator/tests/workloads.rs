@@ -0,0 +1,140 @@
+//! Each workload run end to end against the real node binaries, and checked
+//!
+//! The binaries live in other packages, so they're built here rather than by
+//! `CARGO_BIN_EXE_<name>`, into the same target directory as this test.
+
+use std::{path::PathBuf, process::Command, sync::Mutex, time::Duration};
+
+use checker::{History, Report, Targets};
+use simulator::{NetworkConfig, Run};
+
+/// Build `name`'s binary, with the same profile as this test, and return its path
+fn bin(name: &str) -> PathBuf {
+    // Concurrent builds would only queue up on cargo's lock anyway
+    static BUILD: Mutex<()> = Mutex::new(());
+    let _guard = BUILD.lock().unwrap_or_else(|e| e.into_inner());
+
+    // This test runs from <target>/<profile>/deps
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().unwrap().parent().unwrap().to_owned();
+    let target_dir = profile_dir.parent().unwrap();
+
+    let mut build = Command::new(env!("CARGO"));
+    build
+        .args(["build", "--package", name, "--bin", name, "--target-dir"])
+        .arg(target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"));
+    if profile_dir.ends_with("release") {
+        build.arg("--release");
+    }
+
+    let status = build.status().unwrap();
+    assert!(status.success(), "Could not build {name}");
+
+    profile_dir.join(name)
+}
+
+fn check(run: &Run) {
+    let net = run.run().unwrap();
+    let history = History {
+        events: net.history(),
+    };
+
+    let report = Report::new(&history, &Targets::default());
+    assert!(report.is_valid(), "{report}");
+    assert!(report.stats.ops > 0, "{report}");
+}
+
+fn run(workload: &str, bin_name: &str) -> Run {
+    Run {
+        node_count: 3,
+        time_limit: Duration::from_secs(2),
+        rate: 50.0,
+        ..Run::new(workload, bin(bin_name))
+    }
+}
+
+#[test]
+fn echo() {
+    check(&run("echo", "echo"));
+}
+
+#[test]
+fn unique_ids() {
+    check(&run("unique-ids", "unique-ids"));
+}
+
+#[test]
+fn broadcast() {
+    check(&Run {
+        node_count: 5,
+        ..run("broadcast", "broadcast")
+    });
+}
+
+#[test]
+fn broadcast_with_latency() {
+    check(&Run {
+        node_count: 5,
+        network: NetworkConfig {
+            latency: Duration::from_millis(50),
+            ..NetworkConfig::default()
+        },
+        ..run("broadcast", "broadcast")
+    });
+}
+
+#[test]
+fn broadcast_through_partitions() {
+    check(&Run {
+        node_count: 5,
+        time_limit: Duration::from_secs(4),
+        partition_every: Some(Duration::from_secs(1)),
+        settle: Duration::from_secs(8),
+        ..run("broadcast", "broadcast")
+    });
+}
+
+#[test]
+fn broadcast_with_loss() {
+    check(&Run {
+        node_count: 5,
+        network: NetworkConfig {
+            loss: 0.2,
+            ..NetworkConfig::default()
+        },
+        settle: Duration::from_secs(8),
+        ..run("broadcast", "broadcast")
+    });
+}
+
+#[test]
+fn g_counter() {
+    check(&run("g-counter", "g-counter"));
+}
+
+#[test]
+fn kafka() {
+    check(&run("kafka", "kafka"));
+}
+
+#[test]
+fn kitchen_sink_echo() {
+    check(&run("echo", "kitchen-sink"));
+}
+
+#[test]
+fn kitchen_sink_unique_ids() {
+    check(&run("unique-ids", "kitchen-sink"));
+}
+
+#[test]
+fn kitchen_sink_broadcast_through_partitions() {
+    check(&Run {
+        node_count: 5,
+        time_limit: Duration::from_secs(4),
+        partition_every: Some(Duration::from_secs(1)),
+        settle: Duration::from_secs(8),
+        ..run("broadcast", "kitchen-sink")
+    });
+}
@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+
+set -e
+
+cargo build
+java -jar ~/maelstrom/lib/maelstrom.jar test \
+  -w txn-rw-register \
+  --bin ~/Projects/gossip-glomers/target/debug/txn \
+  --node-count 1 \
+  --time-limit 20 \
+  --rate 1000 \
+  --concurrency 2n \
+  --consistency-models read-uncommitted \
+  --availability total
@@ -0,0 +1,15 @@
+#!/usr/bin/env bash
+
+set -e
+
+cargo build
+java -jar ~/maelstrom/lib/maelstrom.jar test \
+  -w txn-rw-register \
+  --bin ~/Projects/gossip-glomers/target/debug/txn \
+  --node-count 2 \
+  --concurrency 2n \
+  --time-limit 20 \
+  --rate 1000 \
+  --consistency-models read-uncommitted \
+  --availability total \
+  --nemesis partition
@@ -0,0 +1,98 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use color_eyre::Result;
+use common::{
+    Body, IdGenerator, Message, MsgId, MsgIdAble, Node, NodeIdable, Outbox, TimerId, Timers,
+};
+
+use crate::{Txn, TxnRequest, Write};
+
+/// How long to wait for an ack after each send, times the number of sends so far
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+/// Sends after this many stop pushing the retry back any further
+const MAX_BACKOFF_ATTEMPTS: u32 = 5;
+
+/// A batch of writes to get onto one peer, resent until the peer acks it
+#[derive(Debug, Clone)]
+pub struct Job {
+    writes: Vec<Write>,
+    dest: String,
+    attempts: u32,
+    /// The timer that resends it if no ack turns up
+    timer: Option<TimerId>,
+}
+
+/// Replicates writes to every peer, the same way `broadcast`'s `GossipManager` spreads
+/// values: one job per batch per peer, retried until acknowledged. Peers that are
+/// partitioned away simply keep their jobs until the partition heals.
+pub struct GossipManager {
+    outbox: Outbox,
+    node_id: String,
+    peers: Vec<String>,
+    ids: Arc<IdGenerator>,
+    jobs: HashMap<MsgId, Job>,
+}
+
+impl GossipManager {
+    pub fn new(outbox: Outbox, node: &Node) -> Self {
+        Self {
+            outbox,
+            node_id: node.node_id().to_owned(),
+            peers: node
+                .peers
+                .iter()
+                .filter(|p| *p != node.node_id())
+                .cloned()
+                .collect(),
+            ids: Arc::clone(&node.ids),
+            jobs: HashMap::new(),
+        }
+    }
+
+    /// Send `writes` to every peer, as one batch each
+    pub fn replicate(&mut self, writes: Vec<Write>, timers: &mut Timers<Txn>) -> Result<()> {
+        for dest in self.peers.clone() {
+            let msg_id = self.ids.generate_msg_id();
+            let job = Job {
+                writes: writes.clone(),
+                dest,
+                attempts: 0,
+                timer: None,
+            };
+
+            self.jobs.insert(msg_id, job);
+            self.send(msg_id, timers)?;
+        }
+
+        Ok(())
+    }
+
+    /// The peer has the batch sent as `msg_id`, so stop resending it
+    pub fn acked(&mut self, msg_id: MsgId, timers: &mut Timers<Txn>) {
+        if let Some(timer) = self.jobs.remove(&msg_id).and_then(|job| job.timer) {
+            timers.cancel(timer);
+        }
+    }
+
+    /// Send the batch `msg_id`, unless it has been acked, and set a timer to resend it
+    pub(crate) fn send(&mut self, msg_id: MsgId, timers: &mut Timers<Txn>) -> Result<()> {
+        let Some(job) = self.jobs.get_mut(&msg_id) else {
+            return Ok(());
+        };
+
+        let body = Body {
+            msg_id: Some(msg_id),
+            ..Body::new(TxnRequest::Replicate {
+                writes: job.writes.clone(),
+            })
+        };
+        self.outbox
+            .send(&Message::new(&self.node_id, &job.dest, body))?;
+
+        job.attempts += 1;
+        let delay = RETRY_DELAY * job.attempts.min(MAX_BACKOFF_ATTEMPTS);
+        job.timer = Some(timers.after(delay, move |txn| txn.resend(msg_id)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use common::*;
+
+mod gossip;
+pub use gossip::*;
+
+mod store;
+pub use store::*;
+
+use color_eyre::eyre::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    #[serde(rename = "r")]
+    Read,
+    #[serde(rename = "w")]
+    Write,
+}
+
+/// One `["r", key, null]` or `["w", key, value]` step of a transaction
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MicroOp(pub OpKind, pub u64, pub Option<u64>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consistency {
+    ReadUncommitted,
+    ReadCommitted,
+}
+
+impl Consistency {
+    /// The mode picked with `--consistency <mode>`, or failing that the `TXN_CONSISTENCY`
+    /// environment variable. Defaults to read-uncommitted
+    fn configured() -> Result<Self> {
+        Ok(
+            match setting("--consistency", "TXN_CONSISTENCY").as_deref() {
+                None | Some("read-uncommitted") => Consistency::ReadUncommitted,
+                Some("read-committed") => Consistency::ReadCommitted,
+                Some(other) => bail!("Unknown consistency mode {other}"),
+            },
+        )
+    }
+}
+
+#[request(reply = TxnReply)]
+pub enum TxnRequest {
+    Txn {
+        txn: Vec<MicroOp>,
+    },
+    Replicate {
+        writes: Vec<Write>,
+    },
+    /// Peers' acks for the writes we replicate come to us like any other message
+    ReplicateOk,
+}
+
+#[reply]
+pub enum TxnReply {
+    Txn { txn: Vec<MicroOp> },
+    Replicate,
+}
+
+mod service;
+pub use service::*;
+
+fn main() -> Result<()> {
+    let consistency = Consistency::configured()?;
+
+    NodeBuilder::init()?.run(Runtime::new(), |node| {
+        let txn = Txn::new(&node, consistency);
+
+        Router::new(node).with_service(txn)
+    })
+}
@@ -0,0 +1,71 @@
+use color_eyre::Result;
+use common::{Body, MsgId, Node, Outbox, Service, Timers};
+
+use crate::{Consistency, GossipManager, Store, TxnReply, TxnRequest};
+
+/// Runs transactions against the local store, and replicates their writes to every peer
+pub struct Txn {
+    store: Store,
+    consistency: Consistency,
+    gossip_manager: GossipManager,
+    timers: Timers<Txn>,
+}
+
+impl Txn {
+    pub fn new(node: &Node, consistency: Consistency) -> Self {
+        Self {
+            store: Store::new(node.id.clone()),
+            consistency,
+            gossip_manager: GossipManager::new(Outbox::Stdout, node),
+            timers: Timers::new(),
+        }
+    }
+
+    /// Run by the timer of the batch sent as `msg_id`, if it's still unacked
+    pub(crate) fn resend(&mut self, msg_id: MsgId) -> Result<()> {
+        self.gossip_manager.send(msg_id, &mut self.timers)
+    }
+}
+
+impl Service for Txn {
+    type Request = TxnRequest;
+
+    fn handle(
+        &mut self,
+        node: &Node,
+        request: &Body<TxnRequest>,
+    ) -> Result<Option<Body<TxnReply>>> {
+        let reply = match &request.payload {
+            TxnRequest::Txn { txn } => {
+                let (txn, writes) = self.store.execute(txn, self.consistency);
+
+                if !writes.is_empty() {
+                    self.gossip_manager.replicate(writes, &mut self.timers)?;
+                }
+
+                TxnReply::Txn { txn }
+            }
+            TxnRequest::Replicate { writes } => {
+                for write in writes {
+                    self.store.apply(write);
+                }
+
+                TxnReply::Replicate
+            }
+            // Peers ack the writes we replicate to them
+            TxnRequest::ReplicateOk => {
+                if let Some(in_reply_to) = request.in_reply_to() {
+                    self.gossip_manager.acked(in_reply_to, &mut self.timers);
+                }
+
+                return Ok(None);
+            }
+        };
+
+        Ok(Some(request.reply(node, reply)))
+    }
+
+    fn timers(&mut self) -> Option<&mut Timers<Self>> {
+        Some(&mut self.timers)
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Consistency, MicroOp, OpKind};
+
+/// Orders writes across nodes. Every write in a transaction shares its transaction's version
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub clock: u64,
+    pub node: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Write {
+    pub key: u64,
+    pub value: u64,
+    pub version: Version,
+}
+
+/// A last-writer-wins register per key
+///
+/// Every node applies writes by version rather than arrival order, so all nodes agree on
+/// the final value of each key and no write ever clobbers a newer one (no dirty writes).
+pub struct Store {
+    node_id: String,
+    clock: u64,
+    values: HashMap<u64, (u64, Version)>,
+}
+
+impl Store {
+    pub fn new(node_id: String) -> Self {
+        Self {
+            node_id,
+            clock: 0,
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn read(&self, key: u64) -> Option<u64> {
+        self.values.get(&key).map(|(value, _)| *value)
+    }
+
+    fn next_version(&mut self) -> Version {
+        self.clock += 1;
+
+        Version {
+            clock: self.clock,
+            node: self.node_id.clone(),
+        }
+    }
+
+    /// Apply a write unless the key already holds a newer one
+    ///
+    /// Writes sharing a version come from the same transaction, so the later one wins.
+    pub fn apply(&mut self, write: &Write) {
+        self.clock = self.clock.max(write.version.clock);
+
+        let newer = match self.values.get(&write.key) {
+            Some((_, current)) => write.version >= *current,
+            None => true,
+        };
+        if newer {
+            self.values
+                .insert(write.key, (write.value, write.version.clone()));
+        }
+    }
+
+    /// Run `txn` against the local store
+    ///
+    /// Returns the transaction with its reads filled in, along with its writes to replicate.
+    /// Read uncommitted applies each write here as it happens, read committed only makes a
+    /// transaction's writes visible all at once when it completes.
+    ///
+    /// Either way the writes go to peers as one batch, in order: they share a version, so
+    /// peers can only tell which write to a key came last from the order they're applied in.
+    pub fn execute(
+        &mut self,
+        txn: &[MicroOp],
+        consistency: Consistency,
+    ) -> (Vec<MicroOp>, Vec<Write>) {
+        let version = self.next_version();
+        let mut results = Vec::with_capacity(txn.len());
+        let mut uncommitted: HashMap<u64, u64> = HashMap::new();
+        let mut writes = vec![];
+
+        for MicroOp(kind, key, value) in txn {
+            match kind {
+                OpKind::Read => {
+                    let read = uncommitted.get(key).copied().or_else(|| self.read(*key));
+                    results.push(MicroOp(OpKind::Read, *key, read));
+                }
+                OpKind::Write => {
+                    let Some(value) = value else {
+                        results.push(MicroOp(OpKind::Write, *key, None));
+                        continue;
+                    };
+                    let write = Write {
+                        key: *key,
+                        value: *value,
+                        version: version.clone(),
+                    };
+
+                    match consistency {
+                        Consistency::ReadUncommitted => self.apply(&write),
+                        Consistency::ReadCommitted => {
+                            uncommitted.insert(*key, *value);
+                        }
+                    }
+                    writes.push(write);
+                    results.push(MicroOp(OpKind::Write, *key, Some(*value)));
+                }
+            }
+        }
+
+        if consistency == Consistency::ReadCommitted {
+            for write in &writes {
+                self.apply(write);
+            }
+        }
+
+        (results, writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(key: u64, value: u64) -> MicroOp {
+        MicroOp(OpKind::Write, key, Some(value))
+    }
+
+    #[test]
+    fn peers_agree_on_repeated_writes_to_a_key() {
+        for consistency in [Consistency::ReadUncommitted, Consistency::ReadCommitted] {
+            let mut origin = Store::new("n0".to_owned());
+            let mut peer = Store::new("n1".to_owned());
+
+            let (_, writes) = origin.execute(&[write(1, 1), write(1, 2)], consistency);
+            for write in &writes {
+                peer.apply(write);
+            }
+
+            assert_eq!(origin.read(1), Some(2), "{consistency:?}");
+            assert_eq!(peer.read(1), Some(2), "{consistency:?}");
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! The `unique-ids` workload: every `generate` is answered with an id no node has handed out
+
+use color_eyre::eyre::Result;
+use common::*;
+use serde_json::{json, Value};
+
+#[request(reply = GenerateReply)]
+pub enum GenerateRequest {
+    Generate,
+}
+
+#[reply]
+pub enum GenerateReply {
+    Generate { id: Value },
+}
+
+/// Ids are this node's id and a counter, so they're unique without talking to other nodes
+#[derive(Default)]
+pub struct UniqueIds {
+    next_id: u64,
+}
+
+impl Service for UniqueIds {
+    type Request = GenerateRequest;
+
+    fn handle(
+        &mut self,
+        node: &Node,
+        request: &Body<GenerateRequest>,
+    ) -> Result<Option<Body<GenerateReply>>> {
+        let id = json!([node.node_id(), self.next_id]);
+        self.next_id += 1;
+
+        Ok(Some(request.reply(node, GenerateReply::Generate { id })))
+    }
+}